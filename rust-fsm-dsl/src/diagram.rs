@@ -1,4 +1,4 @@
-use crate::{parser, Transition};
+use crate::{parser, Hierarchy, Transition};
 
 use proc_macro2::TokenStream;
 use quote::quote;
@@ -45,11 +45,15 @@ pub fn sanitize_expr(expr: &Expr) -> String {
     expr_str.replace(":", "")
 }
 
-pub fn build_diagram(initial_state: &Ident, transitions: &[Transition]) -> TokenStream {
-    // Track transitions per state to detect nested states
-    let mut transitions_per_state: BTreeMap<&Ident, Vec<&Transition>> = BTreeMap::new();
-
+/// Generate the Mermaid lines of the states and the transitions of a state
+/// machine (or of a region).
+pub fn diagram_lines(
+    initial_state: &Ident,
+    hierarchy: &Hierarchy,
+    transitions: &[Transition],
+) -> String {
     // Group transitions by initial state
+    let mut transitions_per_state: BTreeMap<&Ident, Vec<&Transition>> = BTreeMap::new();
     for transition in transitions {
         transitions_per_state
             .entry(transition.initial_state)
@@ -57,10 +61,7 @@ pub fn build_diagram(initial_state: &Ident, transitions: &[Transition]) -> Token
             .push(transition);
     }
 
-    let mut diagram = format!(
-        "///```mermaid\n///stateDiagram-v2\n///    [*] --> {}\n",
-        initial_state
-    );
+    let mut diagram = format!("///    [*] --> {}\n", initial_state);
 
     // Group transitions by (state, input_name) to detect guards
     let mut transitions_by_state_input: BTreeMap<(&Ident, &Ident), Vec<&Transition>> =
@@ -82,39 +83,10 @@ pub fn build_diagram(initial_state: &Ident, transitions: &[Transition]) -> Token
         }
     }
 
-    // Second: generate nested state definitions
-    for (state, state_transitions) in &transitions_per_state {
-        // Collect all self-referencing inputs (those that loop back to the same state)
-        let self_loop_inputs: BTreeSet<&Ident> = state_transitions
-            .iter()
-            .filter(|t| t.final_state == *state)
-            .map(|t| &t.input_value.name)
-            .collect();
-
-        // Check if this is a nested state (multiple self-loop inputs)
-        if self_loop_inputs.len() > 1 {
-            // Generate nested state representation
-            diagram.push_str(&format!("///state {} {{\n", state));
-
-            for input_name in &self_loop_inputs {
-                diagram.push_str(&format!("///    [*] --> {}\n", input_name));
-
-                // Check if this input has a choice state
-                let key = (*state, *input_name);
-                if choice_states_generated.contains(&key) {
-                    // Transition to choice state
-                    let choice_state_name = format!("{}_guard_{}", state, input_name);
-                    diagram.push_str(&format!(
-                        "///    {} --> {}\n",
-                        input_name, choice_state_name
-                    ));
-                } else {
-                    // Normal self-loop
-                    diagram.push_str(&format!("///{} --> [*]\n", input_name));
-                }
-            }
-
-            diagram.push_str("///}\n");
+    // Second: generate the composite states with their substates
+    for (composite, _) in hierarchy.initial_states() {
+        if hierarchy.parent(composite).is_none() {
+            push_composite(&mut diagram, hierarchy, composite, 1);
         }
     }
 
@@ -129,16 +101,6 @@ pub fn build_diagram(initial_state: &Ident, transitions: &[Transition]) -> Token
 
     // Second pass: generate transitions between states
     for (state, state_transitions) in &transitions_per_state {
-        // Collect all self-referencing inputs
-        let self_loop_inputs: BTreeSet<&Ident> = state_transitions
-            .iter()
-            .filter(|t| t.final_state == *state)
-            .map(|t| &t.input_value.name)
-            .collect();
-
-        // Check if this is a nested state
-        let is_nested = self_loop_inputs.len() > 1;
-
         // Group transitions by input to handle guards
         let mut processed_inputs = BTreeSet::new();
 
@@ -157,16 +119,7 @@ pub fn build_diagram(initial_state: &Ident, transitions: &[Transition]) -> Token
                 let choice_state_name = format!("{}_guard_{}", state, input_name);
                 let input_transitions = &transitions_by_state_input[&key];
 
-                // Check if this input is part of a self-loop (any transition returns to same state)
-                let has_self_loop = input_transitions.iter().any(|t| t.final_state == *state);
-
-                // Only generate transition from state to choice state if:
-                // - The state is NOT nested, OR
-                // - The input doesn't have ANY self-loop (all transitions go to different states)
-                // This avoids duplicating transitions that are already inside the nested state
-                if !is_nested || !has_self_loop {
-                    diagram.push_str(&format!("///    {} --> {}\n", state, choice_state_name));
-                }
+                diagram.push_str(&format!("///    {} --> {}\n", state, choice_state_name));
 
                 // Generate transitions from choice state to final states
                 for guarded_transition in input_transitions {
@@ -193,8 +146,8 @@ pub fn build_diagram(initial_state: &Ident, transitions: &[Transition]) -> Token
                     state, transition.final_state, input_name
                 ));
                 processed_inputs.insert(key);
-            } else if !is_nested {
-                // For single self-loops that aren't part of a nested state
+            } else {
+                // Show the input data and the output on self-loops
                 let fields_expr = if !transition.input_value.fields.is_empty() {
                     let fields = &transition.input_value.fields;
                     format!(" ({})", quote! { #fields })
@@ -216,7 +169,12 @@ pub fn build_diagram(initial_state: &Ident, transitions: &[Transition]) -> Token
         }
     }
 
-    diagram.push_str("///```");
+    diagram
+}
+
+/// Render the diagram of a state machine from its lines.
+pub fn render(lines: &str) -> TokenStream {
+    let diagram = format!("///```mermaid\n///stateDiagram-v2\n{lines}///```");
     let diagram: TokenStream = diagram
         .parse()
         .inspect_err(|m_err| eprintln!("Mermaid diagram error: {m_err:?}\n\n{}", diagram))
@@ -227,3 +185,30 @@ pub fn build_diagram(initial_state: &Ident, transitions: &[Transition]) -> Token
         #diagram
     }
 }
+
+/// Render the diagram of a state machine with orthogonal regions as a
+/// composite state containing the regions separated by `--`.
+pub fn render_regions(name: &Ident, regions: &[String]) -> TokenStream {
+    let mut lines = format!("///    state {name} {{\n");
+    lines.push_str(&regions.join("///    --\n"));
+    lines.push_str("///    }\n");
+    render(&lines)
+}
+
+/// Render a composite state as a Mermaid composite state block, declaring its
+/// substates inside of it so that the transitions place them correctly.
+fn push_composite(diagram: &mut String, hierarchy: &Hierarchy, composite: &Ident, level: usize) {
+    let indent = "    ".repeat(level);
+    diagram.push_str(&format!("///{indent}state {composite} {{\n"));
+    if let Some(initial_state) = hierarchy.initial_state(composite) {
+        diagram.push_str(&format!("///{indent}    [*] --> {initial_state}\n"));
+    }
+    for substate in hierarchy.substates(composite) {
+        if hierarchy.is_composite(substate) {
+            push_composite(diagram, hierarchy, substate, level + 1);
+        } else {
+            diagram.push_str(&format!("///{indent}    {substate}\n"));
+        }
+    }
+    diagram.push_str(&format!("///{indent}}}\n"));
+}
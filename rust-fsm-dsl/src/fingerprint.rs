@@ -0,0 +1,179 @@
+use crate::{parser, Hierarchy, Transition};
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use quote::ToTokens;
+use std::collections::{BTreeMap, BTreeSet};
+use syn::{punctuated::Punctuated, token::Comma, Fields, Ident, Type};
+
+/// Compute the fingerprint of a state machine definition: the 64-bit FNV-1a
+/// hash of its initial state, states (with their nesting), inputs, outputs and
+/// transitions.
+///
+/// The hash is computed from the tokens rather than the source text, so it
+/// does not depend on whitespace and comments. The states, the inputs and the
+/// outputs are hashed in the alphabetical order, and the transitions are
+/// ordered by their state and input, only keeping the order of declaration
+/// (i.e. the order of evaluation of the guards) between the transitions for
+/// the same state and input.
+pub fn definition_hash(
+    initial_state: &Ident,
+    hierarchy: &Hierarchy,
+    states: &BTreeMap<&Ident, &Fields>,
+    inputs: &BTreeMap<&Ident, &Punctuated<Type, Comma>>,
+    outputs: &BTreeSet<&Ident>,
+    transitions: &[Transition],
+) -> u64 {
+    let mut hasher = Fnv1a::new();
+
+    hasher.write_str("initial");
+    hasher.write_tokens(initial_state);
+
+    hasher.write_str("states");
+    for (state, fields) in states {
+        hasher.write_tokens(state);
+        for field in fields.iter() {
+            hasher.write_tokens(&field.ident);
+            hasher.write_tokens(&field.ty);
+        }
+        hasher.write_str(";");
+    }
+
+    hasher.write_str("composites");
+    for (composite, initial_state) in hierarchy.initial_states() {
+        hasher.write_tokens(composite);
+        hasher.write_tokens(initial_state);
+    }
+    for state in states.keys() {
+        if let Some(parent) = hierarchy.parent(state) {
+            hasher.write_tokens(*state);
+            hasher.write_tokens(parent);
+        }
+    }
+
+    hasher.write_str("inputs");
+    for (input, fields) in inputs {
+        hasher.write_tokens(input);
+        for field in fields.iter() {
+            hasher.write_tokens(field);
+        }
+        hasher.write_str(";");
+    }
+
+    hasher.write_str("outputs");
+    for output in outputs {
+        hasher.write_tokens(output);
+    }
+
+    hasher.write_str("transitions");
+    let mut transitions = transitions.iter().collect::<Vec<_>>();
+    transitions.sort_by_key(|transition| (transition.initial_state, &transition.input_value.name));
+    for transition in transitions {
+        hasher.write_tokens(transition.initial_state);
+        hasher.write_tokens(&transition.input_value.name);
+        if let Some(timeout) = transition.timeout {
+            hasher.write_str("after");
+            hasher.write_str(&timeout.millis.to_string());
+        }
+        if let Some(guard) = transition.guard {
+            hasher.write_str("if");
+            hasher.write_tokens(&guard.expr);
+        }
+        hasher.write_str("=>");
+        hasher.write_tokens(transition.final_state);
+        match transition.history {
+            Some(parser::HistoryKind::Shallow) => hasher.write_str("H"),
+            Some(parser::HistoryKind::Deep) => hasher.write_str("H*"),
+            None => {}
+        }
+        if let Some(data) = transition.final_state_data {
+            hasher.write_str("data");
+            hasher.write_tokens(data);
+        }
+        match transition.output {
+            Some(parser::OutputSpec::Constant(output)) => hasher.write_tokens(output),
+            Some(parser::OutputSpec::Call(expr)) => hasher.write_tokens(expr),
+            None => {}
+        }
+        hasher.write_str(";");
+    }
+
+    hasher.finish()
+}
+
+/// Compute the fingerprint of a state machine with orthogonal regions from the
+/// names and the fingerprints of its regions, in the order of declaration.
+pub fn regions_hash(regions: &[(&Ident, u64)]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write_str("regions");
+    for (name, hash) in regions {
+        hasher.write_tokens(*name);
+        hasher.write(&hash.to_le_bytes());
+    }
+    hasher.finish()
+}
+
+/// The 64-bit FNV-1a hasher. Unlike the hashers of the standard library, its
+/// output is specified, so the fingerprints are stable between builds.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Hash a string followed by a separator, so that the concatenations of
+    /// different strings are hashed differently.
+    fn write_str(&mut self, s: &str) {
+        self.write(s.as_bytes());
+        self.write(&[0xff]);
+    }
+
+    /// Hash the tokens one by one, ignoring the whitespace between them.
+    fn write_tokens(&mut self, tokens: &impl ToTokens) {
+        self.write_stream(tokens.to_token_stream());
+    }
+
+    fn write_stream(&mut self, tokens: TokenStream) {
+        for token in tokens {
+            match token {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::None => ("", ""),
+                    };
+                    self.write_str(open);
+                    self.write_stream(group.stream());
+                    self.write_str(close);
+                }
+                TokenTree::Ident(ident) => self.write_str(&ident.to_string()),
+                TokenTree::Punct(punct) => {
+                    let mut buf = [0; 4];
+                    self.write(punct.as_char().encode_utf8(&mut buf).as_bytes());
+                    // Tell `&&` from `& &`
+                    let spacing = match punct.spacing() {
+                        Spacing::Joint => b'j',
+                        Spacing::Alone => b'a',
+                    };
+                    self.write(&[spacing, 0xff]);
+                }
+                TokenTree::Literal(literal) => self.write_str(&literal.to_string()),
+            }
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,176 @@
+use crate::parser;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use std::collections::BTreeMap;
+use syn::{Error, Ident, Result};
+
+/// The nesting of the states declared in composite states.
+///
+/// The generated `State` enum only contains the top-level states, and every
+/// composite state holds the enum of its substates (`ConnectedState` for
+/// `Connected`), so the current state is always a path from a top-level state
+/// down to a state without substates.
+pub struct Hierarchy<'a> {
+    /// The composite states directly containing the nested states
+    parents: BTreeMap<&'a Ident, &'a Ident>,
+    /// The initial substates of the composite states
+    initial_states: BTreeMap<&'a Ident, &'a Ident>,
+}
+
+impl<'a> Hierarchy<'a> {
+    /// Collect the nesting of the states. A state is nested in the composite
+    /// state it is declared in, so all declarations of a state must be in the
+    /// same place.
+    pub fn new(
+        composites: &'a [parser::CompositeState],
+        transitions: &'a [parser::TransitionDef],
+    ) -> Result<Self> {
+        let mut initial_states = BTreeMap::new();
+        for composite in composites {
+            if initial_states
+                .insert(&composite.name, &composite.initial_state)
+                .is_some()
+            {
+                return Err(Error::new_spanned(
+                    &composite.name,
+                    format!(
+                        "the composite state `{}` is declared more than once",
+                        composite.name
+                    ),
+                ));
+            }
+        }
+
+        let declarations = composites
+            .iter()
+            .flat_map(|composite| {
+                [
+                    (&composite.name, composite.parent.as_ref()),
+                    (&composite.initial_state, Some(&composite.name)),
+                ]
+            })
+            .chain(
+                transitions
+                    .iter()
+                    .map(|def| (&def.initial_state.name, def.parent.as_ref())),
+            );
+        let mut declared: BTreeMap<&Ident, Option<&Ident>> = BTreeMap::new();
+        for (state, parent) in declarations {
+            match declared.get(state) {
+                Some(declared_parent) if *declared_parent != parent => {
+                    let place = |parent: Option<&Ident>| match parent {
+                        Some(parent) => format!("in `{parent}`"),
+                        None => "at the top level".to_string(),
+                    };
+                    return Err(Error::new_spanned(
+                        state,
+                        format!(
+                            "the state `{state}` is declared both {} and {}",
+                            place(*declared_parent),
+                            place(parent)
+                        ),
+                    ));
+                }
+                _ => {
+                    declared.insert(state, parent);
+                }
+            }
+        }
+
+        let parents = declared
+            .into_iter()
+            .filter_map(|(state, parent)| Some((state, parent?)))
+            .collect();
+        Ok(Self {
+            parents,
+            initial_states,
+        })
+    }
+
+    /// Whether there are any composite states.
+    pub fn is_flat(&self) -> bool {
+        self.initial_states.is_empty()
+    }
+
+    /// Whether the state has substates.
+    pub fn is_composite(&self, state: &Ident) -> bool {
+        self.initial_states.contains_key(state)
+    }
+
+    /// The composite state directly containing the state.
+    pub fn parent(&self, state: &Ident) -> Option<&'a Ident> {
+        self.parents.get(state).copied()
+    }
+
+    /// The composite states containing the state, from the innermost one.
+    pub fn ancestors(&self, state: &'a Ident) -> impl Iterator<Item = &'a Ident> + '_ {
+        std::iter::successors(self.parent(state), |state| self.parent(state))
+    }
+
+    /// The number of the composite states containing the state.
+    pub fn depth(&self, state: &'a Ident) -> usize {
+        self.ancestors(state).count()
+    }
+
+    /// Whether the state is the given state or is nested in it.
+    pub fn is_within(&self, state: &'a Ident, ancestor: &Ident) -> bool {
+        state == ancestor || self.ancestors(state).any(|state| state == ancestor)
+    }
+
+    /// The state without substates entered when entering the given state,
+    /// following the initial substates of the composite states.
+    pub fn initial_leaf(&self, state: &'a Ident) -> &'a Ident {
+        let mut state = state;
+        while let Some(initial_state) = self.initial_states.get(state) {
+            state = initial_state;
+        }
+        state
+    }
+
+    /// The initial substate of the composite state.
+    #[cfg(feature = "diagram")]
+    pub fn initial_state(&self, composite: &Ident) -> Option<&'a Ident> {
+        self.initial_states.get(composite).copied()
+    }
+
+    /// The states directly contained in the composite state.
+    pub fn substates<'b>(&'b self, composite: &'b Ident) -> impl Iterator<Item = &'a Ident> + 'b {
+        self.parents
+            .iter()
+            .filter(move |(_, parent)| **parent == composite)
+            .map(|(state, _)| *state)
+    }
+
+    /// The initial substates of the composite states.
+    pub fn initial_states(&self) -> impl Iterator<Item = (&'a Ident, &'a Ident)> + '_ {
+        self.initial_states
+            .iter()
+            .map(|(composite, initial_state)| (*composite, *initial_state))
+    }
+
+    /// The enum containing the variant of the state.
+    pub fn enum_path(&self, state: &Ident) -> TokenStream {
+        match self.parent(state) {
+            Some(parent) => {
+                let enum_name = substate_enum(parent);
+                quote!(#enum_name)
+            }
+            None => quote!(Self::State),
+        }
+    }
+
+    /// Nest a pattern matching (or an expression building) the variant of the
+    /// state into the variants of the composite states containing it.
+    pub fn nest(&self, state: &'a Ident, inner: TokenStream) -> TokenStream {
+        self.ancestors(state).fold(inner, |inner, ancestor| {
+            let enum_path = self.enum_path(ancestor);
+            quote! { #enum_path::#ancestor(#inner) }
+        })
+    }
+}
+
+/// The name of the enum of the substates of the composite state.
+pub fn substate_enum(composite: &Ident) -> Ident {
+    format_ident!("{}State", composite)
+}
@@ -5,11 +5,22 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use quote::{quote, ToTokens};
-use std::{collections::BTreeMap, collections::BTreeSet, iter::FromIterator};
-use syn::{parse_macro_input, punctuated::Punctuated, token::Comma, Attribute, Ident, Type};
+use quote::{quote, quote_spanned, ToTokens};
+use std::{cmp::Reverse, collections::BTreeMap, collections::BTreeSet, iter::FromIterator};
+use syn::{
+    parse_macro_input, punctuated::Punctuated, token::Comma, Attribute, Error, Expr, Fields, Ident,
+    Type,
+};
 
+mod fingerprint;
+mod hierarchy;
+mod lints;
+mod minimize;
 mod parser;
+mod regions;
+
+use hierarchy::{substate_enum, Hierarchy};
+use regions::RegionParts;
 
 #[cfg(feature = "diagram")]
 mod diagram;
@@ -19,8 +30,11 @@ mod diagram;
 struct Transition<'a> {
     initial_state: &'a Ident,
     input_value: &'a parser::InputVariant,
+    timeout: &'a Option<parser::Timeout>,
     guard: &'a Option<parser::Guard>,
     final_state: &'a Ident,
+    final_state_data: &'a Option<Expr>,
+    history: Option<parser::HistoryKind>,
     output: &'a Option<parser::OutputSpec>,
 }
 
@@ -35,52 +49,227 @@ fn attrs_to_token_stream(attrs: Vec<Attribute>) -> proc_macro2::TokenStream {
 pub fn state_machine(tokens: TokenStream) -> TokenStream {
     let input = parse_macro_input!(tokens as parser::StateMachineDef);
 
+    if input.regions.is_empty() {
+        generate(input, None).into()
+    } else {
+        regions::generate(input).into()
+    }
+}
+
+/// Generate the module of a state machine. The module of a region uses the
+/// input and the output types of the enclosing module, and reports its
+/// definition hash and diagram to it.
+fn generate(
+    input: parser::StateMachineDef,
+    region: Option<&mut RegionParts>,
+) -> proc_macro2::TokenStream {
     let doc = attrs_to_token_stream(input.doc);
+    // `repr` only applies to the enums, not to the `Impl` struct
+    let repr_u8 = input.attributes.iter().any(is_repr_u8);
+    let impl_attrs = attrs_to_token_stream(
+        input
+            .attributes
+            .iter()
+            .filter(|attr| !attr.path().is_ident("repr"))
+            .cloned()
+            .collect(),
+    );
     let attrs = attrs_to_token_stream(input.attributes);
 
-    if input.transitions.is_empty() {
+    if input
+        .transitions
+        .iter()
+        .all(|def| def.transitions.is_empty())
+    {
         let output = quote! {
             compile_error!("rust-fsm: at least one state transition must be provided");
         };
-        return output.into();
+        return output;
+    }
+
+    // `serde` derives only apply to the generated enums
+    for (listed, custom_type) in [
+        (&input.serde.state, input.state_type.is_some()),
+        (&input.serde.input, input.input_type.is_some()),
+        (&input.serde.output, input.output_type.is_some()),
+    ] {
+        if let Some(ident) = listed.as_ref().filter(|_| custom_type) {
+            return Error::new_spanned(
+                ident,
+                format!("`serde` cannot be derived for the custom `{ident}` type"),
+            )
+            .to_compile_error();
+        }
+    }
+    let input_serde = serde_derive(&input.serde.input);
+    let state_serde = serde_derive(&input.serde.state);
+    let output_serde = serde_derive(&input.serde.output);
+
+    let hierarchy = match Hierarchy::new(&input.composites, &input.transitions) {
+        Ok(hierarchy) => hierarchy,
+        Err(err) => return err.to_compile_error(),
+    };
+    if let (Some(composite), Some(_)) = (input.composites.first(), &input.state_type) {
+        return Error::new_spanned(
+            &composite.name,
+            "composite states are not supported with a custom state type",
+        )
+        .to_compile_error();
     }
 
     let fsm_name = input.name;
     let visibility = input.visibility;
 
     // Collect all transitions first
-    let transitions = input
+    let mut transitions = input
         .transitions
         .iter()
         .flat_map(|def| {
             def.transitions.iter().map(move |transition| Transition {
-                initial_state: &def.initial_state,
+                initial_state: &def.initial_state.name,
                 input_value: &transition.input_value,
+                timeout: &transition.timeout,
                 guard: &transition.guard,
                 final_state: &transition.final_state,
+                final_state_data: &transition.final_state_data,
+                history: transition.history,
                 output: &transition.output,
             })
         })
         .collect::<Vec<_>>();
+    // Match the transitions of the nested states first, so the composite
+    // states only handle the inputs their substates do not
+    transitions.sort_by_key(|transition| Reverse(hierarchy.depth(transition.initial_state)));
+
+    // Collect the data carried by the states. A state carrying data must be
+    // declared as such at least once, all its other mentions may omit it.
+    let unit_fields = Fields::Unit;
+    let mut states: BTreeMap<&Ident, &Fields> = BTreeMap::new();
+    for def in &input.transitions {
+        let parser::StateVariant { name, fields } = &def.initial_state;
+        if matches!(fields, Fields::Unit) {
+            continue;
+        }
+        if hierarchy.is_composite(name) {
+            return Error::new_spanned(
+                fields,
+                format!("the composite state `{name}` cannot carry any data"),
+            )
+            .to_compile_error();
+        }
+        match states.get(name) {
+            Some(declared)
+                if declared.to_token_stream().to_string()
+                    != fields.to_token_stream().to_string() =>
+            {
+                return Error::new_spanned(
+                    fields,
+                    format!("conflicting data declarations for the state `{name}`"),
+                )
+                .to_compile_error();
+            }
+            _ => {
+                states.insert(name, fields);
+            }
+        }
+    }
+
+    if states.contains_key(&input.initial_state) {
+        return Error::new_spanned(
+            &input.initial_state,
+            "the initial state cannot carry any data",
+        )
+        .to_compile_error();
+    }
+    for (composite, initial_state) in hierarchy.initial_states() {
+        if states.contains_key(initial_state) {
+            return Error::new_spanned(
+                initial_state,
+                format!("the initial substate of `{composite}` cannot carry any data"),
+            )
+            .to_compile_error();
+        }
+    }
 
-    let mut states = BTreeSet::new();
     let mut inputs: BTreeMap<&Ident, &Punctuated<Type, Comma>> = BTreeMap::new();
     let mut outputs = BTreeSet::new();
     let mut transition_cases = Vec::new();
     let mut output_cases = Vec::new();
+    let mut step_cases = Vec::new();
+    // The same cases, but entering the composite states through their history
+    let mut history_transition_cases = Vec::new();
+    let mut history_step_cases = Vec::new();
 
-    states.insert(&input.initial_state);
+    states.entry(&input.initial_state).or_insert(&unit_fields);
+    for (composite, initial_state) in hierarchy.initial_states() {
+        states.entry(composite).or_insert(&unit_fields);
+        states.entry(initial_state).or_insert(&unit_fields);
+    }
+    for def in &input.transitions {
+        states
+            .entry(&def.initial_state.name)
+            .or_insert(&unit_fields);
+    }
+    for transition in &transitions {
+        states
+            .entry(transition.initial_state)
+            .or_insert(&unit_fields);
+        states.entry(transition.final_state).or_insert(&unit_fields);
+    }
 
     // Check if we're using a custom input type
     let using_custom_input = input.input_type.is_some();
+    let using_custom_state = input.state_type.is_some();
+    let using_context = input.context_type.is_some();
+
+    // A single `async` closure (in a guard, an output or a data constructor)
+    // makes the whole state machine asynchronous
+    let is_async = transitions.iter().any(|transition| {
+        transition
+            .guard
+            .as_ref()
+            .is_some_and(|guard| is_async_closure(&guard.expr))
+            || transition
+                .final_state_data
+                .as_ref()
+                .is_some_and(is_async_closure)
+            || matches!(transition.output, Some(parser::OutputSpec::Call(expr)) if is_async_closure(expr))
+    });
+
+    // The composite states entered through their history, which is stored in
+    // the wrapper rather than in the state
+    let mut history_composites = BTreeSet::new();
+    for transition in &transitions {
+        if transition.history.is_none() {
+            continue;
+        }
+        let final_state = transition.final_state;
+        if !hierarchy.is_composite(final_state) {
+            return Error::new_spanned(
+                final_state,
+                format!("the state `{final_state}` has no substates, so it has no history"),
+            )
+            .to_compile_error();
+        }
+        if is_async {
+            return Error::new_spanned(
+                final_state,
+                "history is not supported by asynchronous state machines",
+            )
+            .to_compile_error();
+        }
+        history_composites.insert(final_state);
+    }
 
     for transition in &transitions {
         let Transition {
             initial_state,
             final_state,
+            final_state_data,
             input_value,
             guard,
             output,
+            ..
         } = transition;
 
         let input_name = &input_value.name;
@@ -88,80 +277,146 @@ pub fn state_machine(tokens: TokenStream) -> TokenStream {
         // Generate match cases
         // For generated input types, we know exactly which pattern to use based on the DSL
         // For custom input types, we only support unit variants (without tuple fields)
+        //
+        // Closures (guards, outputs and data constructors) receive the context
+        // (if any), the data of the current state and the input tuple fields,
+        // so these are only bound when some closure needs them. Guards and
+        // data constructors may only read the context, outputs may modify it.
+        let bind_for_transition =
+            guard.is_some() || matches!(final_state_data, Some(Expr::Closure(_)));
+        let bind_for_output =
+            guard.is_some() || matches!(output, Some(parser::OutputSpec::Call(_)));
 
-        // When there's a guard with tuple variant, we need to bind the fields
-        let (input_pattern, guard_expr) = if let Some(guard) = guard {
-            let guard_expr = &guard.expr;
-            let param_names = input_param_names(input_value);
-
-            if param_names.is_empty() {
-                (
-                    quote! { Self::Input::#input_name },
-                    quote! { if #guard_expr },
-                )
-            } else {
-                (
-                    quote! { Self::Input::#input_name(#(ref #param_names),*) },
-                    quote! { if (#guard_expr)(#(#param_names),*) },
-                )
+        let guard_expr = |args: &[proc_macro2::TokenStream]| match guard {
+            Some(guard) if args.is_empty() => {
+                let guard_expr = &guard.expr;
+                quote! { if #guard_expr }
             }
-        } else {
-            // No guard
-            let pattern = if using_custom_input || input_value.fields.is_empty() {
-                // For custom types or unit variants
-                quote! { Self::Input::#input_name }
-            } else {
-                // For generated tuple variants without guard, use (..) pattern
-                quote! { Self::Input::#input_name(..) }
-            };
-            (pattern, proc_macro2::TokenStream::new())
+            Some(guard) => {
+                let guard_call = call_closure(&guard.expr, args);
+                quote! { if #guard_call }
+            }
+            None => proc_macro2::TokenStream::new(),
+        };
+
+        let (transition_pattern, params) = case_pattern(
+            &hierarchy,
+            initial_state,
+            states[initial_state],
+            input_value,
+            using_custom_input,
+            bind_for_transition,
+        );
+        let args = context_args(using_context, quote!(context), &params);
+        let transition_guard = guard_expr(&args);
+        let transition_state_expr = match final_state_expr(
+            &hierarchy,
+            initial_state,
+            final_state,
+            states[final_state],
+            final_state_data.as_ref(),
+            &args,
+        ) {
+            Ok(expr) => expr,
+            Err(err) => return err.to_compile_error(),
         };
 
+        let history_state_expr = |state_expr| match transition.history {
+            Some(kind) => history_state_expr(&hierarchy, final_state, kind),
+            None => state_expr,
+        };
+        let history_transition_state_expr = history_state_expr(transition_state_expr.clone());
+        history_transition_cases.push(quote! {
+          #transition_pattern #transition_guard => {
+            Some(#history_transition_state_expr)
+          },
+        });
         transition_cases.push(quote! {
-          (Self::State::#initial_state, #input_pattern) #guard_expr => {
-            Some(Self::State::#final_state)
+          #transition_pattern #transition_guard => {
+            Some(#transition_state_expr)
+          },
+        });
+
+        // The fused case computes both the new state and the output, so the
+        // guard is evaluated only once
+        let (step_pattern, params) = case_pattern(
+            &hierarchy,
+            initial_state,
+            states[initial_state],
+            input_value,
+            using_custom_input,
+            bind_for_transition || bind_for_output,
+        );
+        let args = context_args(using_context, quote!(&*context), &params);
+        let step_guard = guard_expr(&args);
+        let step_state_expr = match final_state_expr(
+            &hierarchy,
+            initial_state,
+            final_state,
+            states[final_state],
+            final_state_data.as_ref(),
+            &args,
+        ) {
+            Ok(expr) => expr,
+            Err(err) => return err.to_compile_error(),
+        };
+        let step_output_expr = match output {
+            Some(parser::OutputSpec::Constant(output_value)) => {
+                quote! { Some(Self::Output::#output_value) }
+            }
+            Some(parser::OutputSpec::Call(call_expr)) => {
+                let call = call_closure(
+                    call_expr,
+                    &context_args(using_context, quote!(context), &params),
+                );
+                quote! { Some(#call) }
+            }
+            None => quote! { None },
+        };
+        let history_step_state_expr = history_state_expr(step_state_expr.clone());
+        history_step_cases.push(quote! {
+          #step_pattern #step_guard => {
+            let new_state = #history_step_state_expr;
+            Some((new_state, #step_output_expr))
+          },
+        });
+        step_cases.push(quote! {
+          #step_pattern #step_guard => {
+            let new_state = #step_state_expr;
+            Some((new_state, #step_output_expr))
           },
         });
 
         if let Some(output_spec) = output {
+            let (output_pattern, params) = case_pattern(
+                &hierarchy,
+                initial_state,
+                states[initial_state],
+                input_value,
+                using_custom_input,
+                bind_for_output,
+            );
+            let output_guard = guard_expr(&context_args(using_context, quote!(&*context), &params));
+
             match output_spec {
                 parser::OutputSpec::Constant(output_value) => {
                     output_cases.push(quote! {
-                      (Self::State::#initial_state, #input_pattern) #guard_expr => {
+                      #output_pattern #output_guard => {
                         Some(Self::Output::#output_value)
                       },
                     });
                 }
                 parser::OutputSpec::Call(call_expr) => {
-                    // Generate code to call the closure with input tuple fields
-                    let param_names = input_param_names(input_value);
-
-                    let (case_expr, param_names_expr) = if !param_names.is_empty() {
-                        // Pattern to destructure the input with references
-                        let pattern_for_call =
-                            quote! { Self::Input::#input_name(#(ref #param_names),*) };
-
-                        (
-                            quote! { (Self::State::#initial_state, #pattern_for_call) #guard_expr },
-                            quote! { (#(#param_names),*) },
-                        )
-                    } else {
-                        // Unit variant - call closure without arguments
-                        (
-                            quote! { (Self::State::#initial_state, #input_pattern) #guard_expr  },
-                            proc_macro2::TokenStream::new(),
-                        )
-                    };
-
-                    output_cases
-                        .push(quote! { #case_expr => { Some((#call_expr) #param_names_expr) }, });
+                    // Generate code to call the closure with the context, the
+                    // state data and the input tuple fields
+                    let args = context_args(using_context, quote!(context), &params);
+                    output_cases.push(quote! {
+                      #output_pattern #output_guard => { Some((#call_expr)(#(#args),*)) },
+                    });
                 }
             }
         }
 
-        states.insert(initial_state);
-        states.insert(final_state);
-
         // Store input variant with its fields
         inputs.entry(input_name).or_insert(&input_value.fields);
 
@@ -171,12 +426,291 @@ pub fn state_machine(tokens: TokenStream) -> TokenStream {
         }
     }
 
+    // Explain the rejections: group the transitions from a state (and from the
+    // composite states containing it) by the input, the input can only be
+    // rejected by the guards if all transitions of a group are guarded (`None`
+    // otherwise). The guards are listed in the order of their evaluation.
+    let mut rejection_cases = Vec::new();
+    for (&state, &fields) in &states {
+        if hierarchy.is_composite(state) {
+            continue;
+        }
+        let mut guard_groups: Vec<(&parser::InputVariant, Option<Vec<&Expr>>)> = Vec::new();
+        for transition in &transitions {
+            if !hierarchy.is_within(state, transition.initial_state) {
+                continue;
+            }
+            let group = guard_groups
+                .iter_mut()
+                .find(|(input_value, _)| input_value.name == transition.input_value.name);
+            match (group, transition.guard) {
+                (Some((_, Some(guards))), Some(guard)) => guards.push(&guard.expr),
+                (Some((_, guards)), None) => *guards = None,
+                (Some((_, None)), Some(_)) => {}
+                (None, guard) => guard_groups.push((
+                    transition.input_value,
+                    guard.as_ref().map(|guard| vec![&guard.expr]),
+                )),
+            }
+        }
+        for (input_value, guards) in guard_groups {
+            let Some(guards) = guards else {
+                continue;
+            };
+            let (pattern, _) = case_pattern(
+                &hierarchy,
+                state,
+                fields,
+                input_value,
+                using_custom_input,
+                false,
+            );
+            rejection_cases.push(quote! {
+                #pattern => ::rust_fsm::RejectionReason::GuardsRejected(&[#(stringify!(#guards)),*]),
+            });
+        }
+    }
+    let rejection_body = quote! {
+        match (state, input) {
+            #(#rejection_cases)*
+            _ => ::rust_fsm::RejectionReason::NoTransition,
+        }
+    };
+
+    // Generate the timeouts of the states, a state may have only one timeout
+    let mut timeouts: BTreeMap<&Ident, &parser::Timeout> = BTreeMap::new();
+    for transition in &transitions {
+        let Some(timeout) = transition.timeout else {
+            continue;
+        };
+        if is_async {
+            return Error::new_spanned(
+                &timeout.after,
+                "timeouts are not supported by asynchronous state machines",
+            )
+            .to_compile_error();
+        }
+        if hierarchy.is_composite(transition.initial_state) {
+            return Error::new_spanned(
+                &timeout.after,
+                "timeouts are not supported by composite states",
+            )
+            .to_compile_error();
+        }
+        if timeouts.insert(transition.initial_state, timeout).is_some() {
+            return Error::new_spanned(
+                &timeout.after,
+                format!(
+                    "the state `{}` has more than one timeout",
+                    transition.initial_state
+                ),
+            )
+            .to_compile_error();
+        }
+    }
+    let timeout_impl = if timeouts.is_empty() {
+        quote!()
+    } else {
+        let cases = timeouts.iter().map(|(state, timeout)| {
+            let (pattern, _) = state_pattern(&hierarchy, state, states[state], false);
+            let parser::Timeout { millis, input, .. } = timeout;
+            quote! {
+                #pattern => Some((::core::time::Duration::from_millis(#millis), Self::Input::#input)),
+            }
+        });
+        quote! {
+            impl ::rust_fsm::TimeoutImpl for Impl {
+                fn timeout(state: &Self::State) -> Option<(::core::time::Duration, Self::Input)> {
+                    match state {
+                        #(#cases)*
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    }
+                }
+            }
+        }
+    };
+
+    // Generate the state hooks, calling all hooks of a state in the order of
+    // their declaration
+    let mut hooks: BTreeMap<parser::HookKind, BTreeMap<&Ident, Vec<&Expr>>> = BTreeMap::new();
+    let mut composite_hooks: BTreeMap<parser::HookKind, BTreeMap<&Ident, Vec<&Expr>>> =
+        BTreeMap::new();
+    for def in &input.transitions {
+        for hook in &def.hooks {
+            let hooks = if hierarchy.is_composite(&def.initial_state.name) {
+                &mut composite_hooks
+            } else {
+                &mut hooks
+            };
+            hooks
+                .entry(hook.kind)
+                .or_default()
+                .entry(&def.initial_state.name)
+                .or_default()
+                .push(&hook.expr);
+            if is_async_closure(&hook.expr) {
+                return Error::new_spanned(&hook.expr, "hooks cannot be `async`")
+                    .to_compile_error();
+            }
+        }
+    }
+    // The transition hooks of the states are called along with the hooks of
+    // the composite states, from the innermost state
+    let mut transition_hooks = [&mut hooks, &mut composite_hooks]
+        .into_iter()
+        .filter_map(|hooks| hooks.remove(&parser::HookKind::Transition))
+        .flatten()
+        .collect::<Vec<_>>();
+    transition_hooks.sort_by_key(|(state, _)| Reverse(hierarchy.depth(state)));
+    // The asynchronous state machine always has a context, which is unused
+    // unless the context type is set
+    let context_param = if using_context {
+        quote!(context)
+    } else {
+        quote!(_)
+    };
+    let hook_fns = hooks.iter().map(|(kind, state_hooks)| {
+        let cases = state_hooks.iter().map(|(state, exprs)| {
+            let (pattern, params) = state_pattern(&hierarchy, state, states[state], true);
+            let args = context_args(using_context, quote!(context), &params);
+            let calls = exprs.iter().map(|expr| quote! { (#expr)(#(#args),*); });
+            quote! {
+                #pattern => { #(#calls)* }
+            }
+        });
+        let body = quote! {
+            match state {
+                #(#cases)*
+                #[allow(unreachable_patterns)]
+                _ => {}
+            }
+        };
+        match (kind, using_context, is_async) {
+            (parser::HookKind::Enter, false, false) => quote! {
+                fn on_enter(state: &Self::State) { #body }
+            },
+            (parser::HookKind::Exit, false, false) => quote! {
+                fn on_exit(state: &Self::State) { #body }
+            },
+            (parser::HookKind::Enter, true, false) => quote! {
+                fn on_enter_with(state: &Self::State, context: &mut Self::Context) { #body }
+            },
+            (parser::HookKind::Exit, true, false) => quote! {
+                fn on_exit_with(state: &Self::State, context: &mut Self::Context) { #body }
+            },
+            (parser::HookKind::Enter, _, true) => quote! {
+                fn on_enter(state: &Self::State, #context_param: &mut Self::Context) { #body }
+            },
+            (parser::HookKind::Exit, _, true) => quote! {
+                fn on_exit(state: &Self::State, #context_param: &mut Self::Context) { #body }
+            },
+            // Generated along with the hooks of the composite states
+            (parser::HookKind::Transition, _, _) => quote!(),
+        }
+    });
+    let hook_fns = quote! { #(#hook_fns)* };
+
+    // The hooks of a composite state are called on the transitions crossing its
+    // boundary, the exit hooks from the innermost composite state and the
+    // entry hooks from the outermost one. As they are called by the transition
+    // hook, they run after the exit hook of the previous state and before the
+    // entry hook of the new state. The transition hooks of the states run
+    // between them, on every transition from the state (or from any of the
+    // substates of a composite state).
+    let composite_hook_fn = if composite_hooks.is_empty() && transition_hooks.is_empty() {
+        quote!()
+    } else {
+        let args = context_args(using_context, quote!(context), &[]);
+        let hook_calls = |kind, innermost_first: bool| {
+            let mut state_hooks = composite_hooks
+                .get(&kind)
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            state_hooks.sort_by_key(|(state, _)| hierarchy.depth(state));
+            if innermost_first {
+                state_hooks.reverse();
+            }
+            state_hooks
+                .into_iter()
+                .map(|(state, exprs)| {
+                    let (pattern, _) = state_pattern(&hierarchy, state, &unit_fields, false);
+                    let calls = exprs.iter().map(|expr| quote! { (#expr)(#(#args),*); });
+                    let (left, entered) = if kind == parser::HookKind::Exit {
+                        (quote!(from), quote!(to))
+                    } else {
+                        (quote!(to), quote!(from))
+                    };
+                    quote! {
+                        if matches!(#left, #pattern) && !matches!(#entered, #pattern) {
+                            #(#calls)*
+                        }
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let exit_calls = hook_calls(parser::HookKind::Exit, true);
+        let enter_calls = hook_calls(parser::HookKind::Enter, false);
+        let transition_calls = transition_hooks.iter().map(|(state, exprs)| {
+            let (pattern, params) = state_pattern(&hierarchy, state, states[state], true);
+            let args = context_args(using_context, quote!(context), &params);
+            let calls = exprs
+                .iter()
+                .map(|expr| quote! { (#expr)(#(#args,)* input, to); });
+            quote! {
+                if let #pattern = from {
+                    #(#calls)*
+                }
+            }
+        });
+        let body = quote! {
+            #(#exit_calls)*
+            #(#transition_calls)*
+            #(#enter_calls)*
+        };
+        let input_param = if transition_hooks.is_empty() {
+            quote!(_)
+        } else {
+            quote!(input)
+        };
+        match (using_context, is_async) {
+            (false, false) => quote! {
+                fn on_transition(from: &Self::State, #input_param: &Self::Input, to: &Self::State) {
+                    #body
+                }
+            },
+            (true, false) => quote! {
+                fn on_transition_with(
+                    from: &Self::State,
+                    #input_param: &Self::Input,
+                    to: &Self::State,
+                    context: &mut Self::Context,
+                ) {
+                    #body
+                }
+            },
+            (_, true) => quote! {
+                fn on_transition(
+                    from: &Self::State,
+                    #input_param: &Self::Input,
+                    to: &Self::State,
+                    #context_param: &mut Self::Context,
+                ) {
+                    #body
+                }
+            },
+        }
+    };
+    let hook_fns = quote! {
+        #hook_fns
+        #composite_hook_fn
+    };
+
     #[cfg(feature = "diagram")]
-    let diagram = diagram::build_diagram(&input.initial_state, &transitions);
-    #[cfg(not(feature = "diagram"))]
-    let diagram = quote!();
+    let diagram = diagram::diagram_lines(&input.initial_state, &hierarchy, &transitions);
 
-    let initial_state_name = &input.initial_state;
+    let initial_state_expr = unit_state_expr(&hierarchy, &input.initial_state);
 
     // Generate input variants with optional tuple fields
     let input_variants = inputs.iter().map(|(name, fields)| {
@@ -189,10 +723,12 @@ pub fn state_machine(tokens: TokenStream) -> TokenStream {
 
     let (input_type, input_impl) = match input.input_type {
         Some(t) => (quote!(#t), quote!()),
+        None if region.is_some() => (quote!(super::Input), quote!()),
         None => (
             quote!(Input),
             quote! {
                 #attrs
+                #input_serde
                 pub enum Input {
                     #(#input_variants),*
                 }
@@ -200,21 +736,133 @@ pub fn state_machine(tokens: TokenStream) -> TokenStream {
         ),
     };
 
+    // The top-level states are the variants of the `State` enum, the substates
+    // of a composite state are the variants of its own enum
+    let state_variants = |parent: Option<&Ident>| {
+        states
+            .iter()
+            .filter(|(name, _)| hierarchy.parent(name) == parent)
+            .map(|(name, fields)| {
+                if hierarchy.is_composite(name) {
+                    let enum_name = substate_enum(name);
+                    quote! { #name(#enum_name) }
+                } else {
+                    quote! { #name #fields }
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let substate_enums = hierarchy.initial_states().map(|(composite, _)| {
+        let enum_name = substate_enum(composite);
+        let variants = state_variants(Some(composite));
+        quote! {
+            #attrs
+            #state_serde
+            pub enum #enum_name {
+                #(#variants),*
+            }
+        }
+    });
+    let substate_enums = quote! { #(#substate_enums)* };
+    let state_variants = state_variants(None);
+
+    // Fieldless `#[repr(u8)]` states can be stored in an atomic integer
+    let atomic_state_impl =
+        if repr_u8 && hierarchy.is_flat() && states.values().all(|fields| fields.is_empty()) {
+            let names = states.keys();
+            let names_from = states.keys();
+            quote! {
+                impl ::rust_fsm::AtomicState for State {
+                    fn to_u8(&self) -> u8 {
+                        match self {
+                            #(Self::#names => Self::#names as u8),*
+                        }
+                    }
+
+                    fn from_u8(value: u8) -> Option<Self> {
+                        #(if value == Self::#names_from as u8 {
+                            return Some(Self::#names_from);
+                        })*
+                        None
+                    }
+                }
+            }
+        } else {
+            quote!()
+        };
+
+    // The states are named after the variants of the generated `State` enum
+    let snapshot_impl = if is_async {
+        quote!()
+    } else {
+        let version = match &input.version {
+            Some(version) => match version.base10_parse::<u32>() {
+                Ok(version) => version,
+                Err(err) => return err.to_compile_error(),
+            },
+            None => 0,
+        };
+        // The current state is never a composite state, but one of its substates
+        let leaves = states
+            .iter()
+            .filter(|(state, _)| !hierarchy.is_composite(state))
+            .collect::<Vec<_>>();
+        let name_cases = leaves.iter().map(|(state, fields)| {
+            let (pattern, _) = state_pattern(&hierarchy, state, fields, false);
+            let name = state.to_string();
+            quote! { #pattern => #name, }
+        });
+        let from_name_cases =
+            leaves
+                .iter()
+                .filter(|(_, fields)| fields.is_empty())
+                .map(|(state, _)| {
+                    let name = state.to_string();
+                    let state_expr = unit_state_expr(&hierarchy, state);
+                    quote! { #name => Some(#state_expr), }
+                });
+        quote! {
+            impl ::rust_fsm::SnapshotImpl for Impl {
+                const VERSION: u32 = #version;
+
+                fn state_name(state: &Self::State) -> &'static str {
+                    match state {
+                        #(#name_cases)*
+                    }
+                }
+
+                fn state_from_name(name: &str) -> Option<Self::State> {
+                    match name {
+                        #(#from_name_cases)*
+                        _ => None,
+                    }
+                }
+            }
+        }
+    };
+
     let (state_type, state_impl) = match input.state_type {
         Some(t) => (quote!(#t), quote!()),
         None => (
             quote!(State),
             quote! {
                 #attrs
+                #state_serde
                 pub enum State {
-                    #(#states),*
+                    #(#state_variants),*
                 }
+
+                #substate_enums
+
+                #atomic_state_impl
+                #snapshot_impl
             },
         ),
     };
 
     let (output_type, output_impl) = match input.output_type {
         Some(t) => (quote!(#t), quote!()),
+        None if region.is_some() => (quote!(super::Output), quote!()),
         None => {
             // Many attrs and derives may work incorrectly (or simply not work) for empty enums, so we just skip them
             // altogether if the output alphabet is empty.
@@ -227,6 +875,7 @@ pub fn state_machine(tokens: TokenStream) -> TokenStream {
                 quote!(Output),
                 quote! {
                     #attrs
+                    #output_serde
                     pub enum Output {
                         #(#outputs),*
                     }
@@ -235,29 +884,177 @@ pub fn state_machine(tokens: TokenStream) -> TokenStream {
         }
     };
 
+    // Remember the substates of the composite states entered through their
+    // history whenever the machine is in them
+    let history_impl = if history_composites.is_empty() {
+        quote!()
+    } else {
+        let fields = history_composites.iter().map(|composite| {
+            let enum_name = substate_enum(composite);
+            quote! { pub #composite: Option<#enum_name> }
+        });
+        let remember = history_composites.iter().map(|composite| {
+            let enum_path = hierarchy.enum_path(composite);
+            let pattern = hierarchy.nest(composite, quote! { #enum_path::#composite(__substate) });
+            quote! {
+                if let #pattern = state {
+                    history.#composite = Some(::core::clone::Clone::clone(__substate));
+                }
+            }
+        });
+        quote! {
+            /// The substates the composite states entered through their
+            /// history were last in.
+            #impl_attrs
+            #state_serde
+            #[derive(Default)]
+            #[allow(non_snake_case)]
+            pub struct History {
+                #(#fields),*
+            }
+
+            impl ::rust_fsm::HistoryImpl for Impl {
+                type History = History;
+
+                fn remember(history: &mut Self::History, state: &Self::State) {
+                    #(#remember)*
+                }
+
+                fn transition_with_history(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    #context_param: &Self::Context,
+                    history: &Self::History,
+                ) -> Option<Self::State> {
+                    match (state, input) {
+                        #(#history_transition_cases)*
+                        _ => None,
+                    }
+                }
+
+                fn step_with_history(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    #context_param: &mut Self::Context,
+                    history: &Self::History,
+                ) -> Option<(Self::State, Option<Self::Output>)> {
+                    match (state, input) {
+                        #(#history_step_cases)*
+                        _ => None,
+                    }
+                }
+            }
+        }
+    };
+
+    let wrapper_type = if is_async {
+        quote!(::rust_fsm::AsyncStateMachine<Impl>)
+    } else if history_composites.is_empty() {
+        quote!(::rust_fsm::StateMachine<Impl>)
+    } else {
+        quote! {
+            ::rust_fsm::StateMachine<Impl, ::rust_fsm::NoRecorder, ::rust_fsm::SubstateHistory<Impl>>
+        }
+    };
+
     // Collect use statements
     let use_statements = &input.use_statements;
 
-    let output = quote! {
-        #doc
-        #diagram
-        #visibility mod #fsm_name {
-            #(#use_statements)*
+    let state_machine_impl = match input.context_type {
+        context_type if is_async => {
+            let context_type = match context_type {
+                Some(context_type) => quote!(#context_type),
+                None => quote!(()),
+            };
+            quote! {
+                impl ::rust_fsm::AsyncStateMachineImpl for Impl {
+                    type Input = #input_type;
+                    type State = #state_type;
+                    type Output = #output_type;
+                    type Context = #context_type;
+                    const INITIAL_STATE: Self::State = #initial_state_expr;
 
-            #attrs
-            pub struct Impl;
+                    async fn step(
+                        state: &Self::State,
+                        input: &Self::Input,
+                        #context_param: &mut Self::Context,
+                    ) -> Option<(Self::State, Option<Self::Output>)> {
+                        match (state, input) {
+                            #(#step_cases)*
+                            _ => None,
+                        }
+                    }
 
-            pub type StateMachine = ::rust_fsm::StateMachine<Impl>;
+                    fn rejection_reason(
+                        state: &Self::State,
+                        input: &Self::Input,
+                        _: &Self::Context,
+                    ) -> ::rust_fsm::RejectionReason {
+                        #rejection_body
+                    }
 
-            #input_impl
-            #state_impl
-            #output_impl
+                    #hook_fns
+                }
+            }
+        }
+        Some(context_type) => quote! {
+            impl ::rust_fsm::StateMachineContextImpl for Impl {
+                type Input = #input_type;
+                type State = #state_type;
+                type Output = #output_type;
+                type Context = #context_type;
+                const INITIAL_STATE: Self::State = #initial_state_expr;
 
+                fn transition_with(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    context: &Self::Context,
+                ) -> Option<Self::State> {
+                    match (state, input) {
+                        #(#transition_cases)*
+                        _ => None,
+                    }
+                }
+
+                fn output_with(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    context: &mut Self::Context,
+                ) -> Option<Self::Output> {
+                    match (state, input) {
+                        #(#output_cases)*
+                        _ => None,
+                    }
+                }
+
+                fn step_with(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    context: &mut Self::Context,
+                ) -> Option<(Self::State, Option<Self::Output>)> {
+                    match (state, input) {
+                        #(#step_cases)*
+                        _ => None,
+                    }
+                }
+
+                fn rejection_reason_with(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    _: &Self::Context,
+                ) -> ::rust_fsm::RejectionReason {
+                    #rejection_body
+                }
+
+                #hook_fns
+            }
+        },
+        None => quote! {
             impl ::rust_fsm::StateMachineImpl for Impl {
                 type Input = #input_type;
                 type State = #state_type;
                 type Output = #output_type;
-                const INITIAL_STATE: Self::State = Self::State::#initial_state_name;
+                const INITIAL_STATE: Self::State = #initial_state_expr;
 
                 fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
                     match (state, input) {
@@ -272,16 +1069,385 @@ pub fn state_machine(tokens: TokenStream) -> TokenStream {
                         _ => None,
                     }
                 }
+
+                fn step(
+                    state: &Self::State,
+                    input: &Self::Input,
+                ) -> Option<(Self::State, Option<Self::Output>)> {
+                    match (state, input) {
+                        #(#step_cases)*
+                        _ => None,
+                    }
+                }
+
+                fn rejection_reason(
+                    state: &Self::State,
+                    input: &Self::Input,
+                ) -> ::rust_fsm::RejectionReason {
+                    #rejection_body
+                }
+
+                #hook_fns
             }
+        },
+    };
+
+    let definition_hash = fingerprint::definition_hash(
+        &input.initial_state,
+        &hierarchy,
+        &states,
+        &inputs,
+        &outputs,
+        &transitions,
+    );
+
+    // The states and the inputs can only be listed if they carry no data
+    let introspect_impl = if using_custom_input
+        || using_custom_state
+        || using_context
+        || is_async
+        || states.values().any(|fields| !fields.is_empty())
+        || inputs.values().any(|fields| !fields.is_empty())
+    {
+        quote!()
+    } else {
+        let leaves = states
+            .keys()
+            .filter(|state| !hierarchy.is_composite(state))
+            .map(|state| unit_state_expr(&hierarchy, state));
+        let input_names = inputs.keys();
+        quote! {
+            impl ::rust_fsm::IntrospectImpl for Impl {
+                const STATES: &'static [Self::State] = &[#(#leaves),*];
+                const INPUTS: &'static [Self::Input] = &[#(Self::Input::#input_names),*];
+            }
+        }
+    };
+
+    // There is no stable way to emit a warning from a procedural macro, so the
+    // equivalent states are reported by using a deprecated constant
+    let minimize_items = match &input.minimize {
+        Some(minimize) => {
+            let groups =
+                minimize::equivalent_states(&hierarchy, &states, &input.transitions, &transitions);
+            let group_names = groups.iter().map(|group| {
+                let names = group.iter().map(ToString::to_string);
+                quote! { &[#(#names),*] }
+            });
+            let warning = if groups.is_empty() {
+                quote!()
+            } else {
+                let note = minimize::warning(&groups);
+                let usage = quote_spanned! {minimize.span()=>
+                    const _: () = minimize;
+                };
+                quote! {
+                    #[deprecated(note = #note)]
+                    #[allow(non_upper_case_globals)]
+                    const minimize: () = ();
+                    #usage
+                }
+            };
+            quote! {
+                /// The groups of the equivalent states, which can be merged
+                /// without changing the behavior of the state machine.
+                pub const EQUIVALENT_STATES: &[&[&str]] = &[#(#group_names),*];
+                #warning
+            }
+        }
+        None => quote!(),
+    };
+
+    // The final states of a state machine with regions may be declared in any
+    // of its regions, so they are checked by `regions::generate`
+    if region.is_none() {
+        if let Some(state) = input
+            .final_states
+            .iter()
+            .find(|state| !states.contains_key(state))
+        {
+            return Error::new_spanned(state, format!("the state `{state}` is not declared"))
+                .to_compile_error();
+        }
+    }
+    let lint_warnings = match lints::check(
+        &input.initial_state,
+        &hierarchy,
+        &states,
+        &transitions,
+        &input.final_states,
+        &input.lints,
+    ) {
+        Ok(warnings) => warnings,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    // The diagram of a region is a part of the diagram of the enclosing module
+    let diagram = match region {
+        Some(parts) => {
+            parts.definition_hash = definition_hash;
+            #[cfg(feature = "diagram")]
+            {
+                parts.diagram = diagram;
+            }
+            quote!()
         }
+        #[cfg(feature = "diagram")]
+        None => diagram::render(&diagram),
+        #[cfg(not(feature = "diagram"))]
+        None => quote!(),
     };
 
-    output.into()
+    let output = quote! {
+        #doc
+        #diagram
+        #visibility mod #fsm_name {
+            #(#use_statements)*
+
+            /// The fingerprint of the state machine definition, which changes
+            /// whenever its states, inputs, outputs or transitions change.
+            pub const DEFINITION_HASH: u64 = #definition_hash;
+
+            #impl_attrs
+            pub struct Impl;
+
+            pub type StateMachine = #wrapper_type;
+
+            #input_impl
+            #state_impl
+            #output_impl
+
+            #state_machine_impl
+            #timeout_impl
+            #history_impl
+            #introspect_impl
+            #minimize_items
+            #lint_warnings
+        }
+    };
+
+    output
+}
+
+/// Generate the `serde` derives of a generated enum if it is listed in
+/// `#[state_machine(serde(..))]`.
+fn serde_derive(listed: &Option<Ident>) -> proc_macro2::TokenStream {
+    if listed.is_some() {
+        quote! {
+            #[derive(::rust_fsm::serde::Serialize, ::rust_fsm::serde::Deserialize)]
+            #[serde(crate = "::rust_fsm::serde")]
+        }
+    } else {
+        quote!()
+    }
 }
 
 /// Generate parameter names: __arg0, __arg1, etc.
 fn input_param_names(input: &parser::InputVariant) -> Vec<Ident> {
-    (0..input.fields.len())
-        .map(|i| Ident::new(&format!("__arg{i}"), proc_macro2::Span::call_site()))
+    param_names("__arg", input.fields.len())
+}
+
+fn param_names(prefix: &str, count: usize) -> Vec<Ident> {
+    (0..count)
+        .map(|i| Ident::new(&format!("{prefix}{i}"), proc_macro2::Span::call_site()))
         .collect()
 }
+
+/// Whether the attribute is `#[repr(u8)]`.
+fn is_repr_u8(attr: &Attribute) -> bool {
+    let mut repr_u8 = false;
+    if attr.path().is_ident("repr") {
+        // Other representation hints (e.g. `align(..)`) are not relevant here
+        let _ = attr.parse_nested_meta(|meta| {
+            repr_u8 |= meta.path.is_ident("u8");
+            Ok(())
+        });
+    }
+    repr_u8
+}
+
+/// Whether the expression is an `async` closure. A single `async` closure
+/// makes the whole state machine asynchronous.
+fn is_async_closure(expr: &Expr) -> bool {
+    matches!(expr, Expr::Closure(closure) if closure.asyncness.is_some())
+}
+
+/// Generate the call of a closure with the given arguments, awaiting the
+/// result of `async` closures.
+fn call_closure(expr: &Expr, args: &[proc_macro2::TokenStream]) -> proc_macro2::TokenStream {
+    if is_async_closure(expr) {
+        quote! { (#expr)(#(#args),*).await }
+    } else {
+        quote! { (#expr)(#(#args),*) }
+    }
+}
+
+/// Prepend the context argument (if the context is used) to the arguments
+/// bound by a match case.
+fn context_args(
+    using_context: bool,
+    context: proc_macro2::TokenStream,
+    params: &[Ident],
+) -> Vec<proc_macro2::TokenStream> {
+    using_context
+        .then_some(context)
+        .into_iter()
+        .chain(params.iter().map(ToTokens::into_token_stream))
+        .collect()
+}
+
+/// Generate the pattern matching a state. If `bind` is set, the data of the
+/// state is bound by reference to the returned names. The pattern of a
+/// composite state matches all of its substates.
+fn state_pattern<'a>(
+    hierarchy: &Hierarchy<'a>,
+    state: &'a Ident,
+    fields: &Fields,
+    bind: bool,
+) -> (proc_macro2::TokenStream, Vec<Ident>) {
+    let params = if bind {
+        param_names("__state", fields.len())
+    } else {
+        Vec::new()
+    };
+    let enum_path = hierarchy.enum_path(state);
+    let pattern = match fields {
+        _ if hierarchy.is_composite(state) => quote! { #enum_path::#state(_) },
+        Fields::Unit => quote! { #enum_path::#state },
+        _ if !bind => match fields {
+            Fields::Named(_) => quote! { #enum_path::#state { .. } },
+            _ => quote! { #enum_path::#state(..) },
+        },
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote! { #enum_path::#state { #(#names: ref #params),* } }
+        }
+        Fields::Unnamed(_) => quote! { #enum_path::#state(#(ref #params),*) },
+    };
+    (hierarchy.nest(state, pattern), params)
+}
+
+/// Generate the expression building a state without data. Entering a
+/// composite state enters its initial substate.
+fn unit_state_expr<'a>(hierarchy: &Hierarchy<'a>, state: &'a Ident) -> proc_macro2::TokenStream {
+    let leaf = hierarchy.initial_leaf(state);
+    let enum_path = hierarchy.enum_path(leaf);
+    hierarchy.nest(leaf, quote! { #enum_path::#leaf })
+}
+
+/// Generate the expression entering a composite state through its history: the
+/// remembered substate is resumed (only down to the direct substate for the
+/// shallow history), or the initial substate is entered if there is none.
+fn history_state_expr<'a>(
+    hierarchy: &Hierarchy<'a>,
+    composite: &'a Ident,
+    kind: parser::HistoryKind,
+) -> proc_macro2::TokenStream {
+    let enum_path = hierarchy.enum_path(composite);
+    let resumed = hierarchy.nest(
+        composite,
+        quote! { #enum_path::#composite(::core::clone::Clone::clone(__substate)) },
+    );
+    let initial = unit_state_expr(hierarchy, composite);
+    let enum_name = substate_enum(composite);
+    let shallow_cases = hierarchy
+        .substates(composite)
+        .filter(|substate| kind == parser::HistoryKind::Shallow && hierarchy.is_composite(substate))
+        .map(|substate| {
+            let state_expr = unit_state_expr(hierarchy, substate);
+            quote! { Some(#enum_name::#substate(_)) => #state_expr, }
+        });
+    quote! {
+        match &history.#composite {
+            #(#shallow_cases)*
+            Some(__substate) => #resumed,
+            None => #initial,
+        }
+    }
+}
+
+/// Generate the `(state, input)` pattern of a match case. If `bind` is set,
+/// the data of the state and the input tuple fields are bound by reference to
+/// the returned names (state data first).
+fn case_pattern<'a>(
+    hierarchy: &Hierarchy<'a>,
+    state: &'a Ident,
+    state_fields: &Fields,
+    input: &parser::InputVariant,
+    using_custom_input: bool,
+    bind: bool,
+) -> (proc_macro2::TokenStream, Vec<Ident>) {
+    let (state_pattern, state_params) = state_pattern(hierarchy, state, state_fields, bind);
+
+    let input_name = &input.name;
+    let (input_pattern, input_params) = if bind && !input.fields.is_empty() {
+        let param_names = input_param_names(input);
+        (
+            quote! { Self::Input::#input_name(#(ref #param_names),*) },
+            param_names,
+        )
+    } else if using_custom_input || input.fields.is_empty() {
+        // For custom types or unit variants
+        (quote! { Self::Input::#input_name }, Vec::new())
+    } else {
+        // For generated tuple variants without bindings, use (..) pattern
+        (quote! { Self::Input::#input_name(..) }, Vec::new())
+    };
+
+    let mut params = state_params;
+    params.extend(input_params);
+    (quote! { (#state_pattern, #input_pattern) }, params)
+}
+
+/// Generate the expression building the final state of a transition. The data
+/// of a state is either built by the provided closure called with `args`, set
+/// by the provided expression, or copied from the initial state when the
+/// transition loops back to the same state.
+fn final_state_expr<'a>(
+    hierarchy: &Hierarchy<'a>,
+    initial_state: &Ident,
+    final_state: &'a Ident,
+    fields: &Fields,
+    data: Option<&Expr>,
+    args: &[proc_macro2::TokenStream],
+) -> syn::Result<proc_macro2::TokenStream> {
+    let data = match (data, fields) {
+        (None, Fields::Unit) => return Ok(unit_state_expr(hierarchy, final_state)),
+        (Some(data), Fields::Unit) => {
+            return Err(Error::new_spanned(
+                data,
+                format!("the state `{final_state}` does not carry any data"),
+            ))
+        }
+        (None, _) if initial_state == final_state => {
+            return Ok(quote! { ::core::clone::Clone::clone(state) })
+        }
+        (None, _) => {
+            return Err(Error::new_spanned(
+                final_state,
+                format!(
+                    "the state `{final_state}` carries data, so it must be built: `{final_state}(|..| ..)`"
+                ),
+            ))
+        }
+        (Some(closure @ Expr::Closure(_)), _) => call_closure(closure, args),
+        (Some(data), _) => quote! { #data },
+    };
+
+    let values = param_names("__value", fields.len());
+    let enum_path = hierarchy.enum_path(final_state);
+    let construct = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote! { #enum_path::#final_state { #(#names: #values),* } }
+        }
+        _ => quote! { #enum_path::#final_state(#(#values),*) },
+    };
+    let construct = hierarchy.nest(final_state, construct);
+
+    Ok(if values.len() == 1 {
+        quote! {{ let #(#values)* = #data; #construct }}
+    } else {
+        quote! {{ let (#(#values),*) = #data; #construct }}
+    })
+}
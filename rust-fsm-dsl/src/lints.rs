@@ -0,0 +1,233 @@
+use crate::{Hierarchy, Transition};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use std::collections::{BTreeMap, BTreeSet};
+use syn::{meta::ParseNestedMeta, Error, Fields, Ident, Result};
+
+/// What to do when a lint finds a problem.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+/// The levels of the lints of the state machine definition, set with
+/// `#[state_machine(allow(..), warn(..), deny(..))]`.
+#[derive(Clone, Default)]
+pub struct Lints {
+    /// The states that cannot be reached from the initial state
+    pub unreachable_states: LintLevel,
+    /// The states without outgoing transitions not declared as final. Unless
+    /// it is set, the lint only warns about the state machines declaring their
+    /// final states, as the others may end in any state.
+    pub dead_end_states: Option<LintLevel>,
+    /// The transitions declared after a transition without a guard from the
+    /// same state with the same input, which are never taken
+    pub shadowed_transitions: LintLevel,
+}
+
+impl Lints {
+    /// Parse the lints listed in `allow(..)`, `warn(..)` or `deny(..)`.
+    pub fn parse(&mut self, level: LintLevel, meta: ParseNestedMeta) -> Result<()> {
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("unreachable_states") {
+                self.unreachable_states = level;
+            } else if meta.path.is_ident("dead_end_states") {
+                self.dead_end_states = Some(level);
+            } else if meta.path.is_ident("shadowed_transitions") {
+                self.shadowed_transitions = level;
+            } else {
+                return Err(meta.error(
+                    "expected `unreachable_states`, `dead_end_states` or `shadowed_transitions`",
+                ));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Check the states of the state machine: the states (without substates) must
+/// be reachable from the initial state, and must have outgoing transitions
+/// (including the inherited ones) unless they are declared as final. Then
+/// check that every transition can be taken.
+///
+/// There is no stable way to emit a warning from a procedural macro, so the
+/// warnings are emitted by using deprecated constants named after the states
+/// (and the inputs of the shadowed transitions). The denied lints are reported
+/// as errors.
+pub fn check<'a>(
+    initial_state: &'a Ident,
+    hierarchy: &Hierarchy<'a>,
+    states: &BTreeMap<&'a Ident, &Fields>,
+    transitions: &[Transition<'a>],
+    final_states: &[Ident],
+    lints: &Lints,
+) -> Result<TokenStream> {
+    let leaves = states
+        .keys()
+        .filter(|state| !hierarchy.is_composite(state))
+        .copied()
+        .collect::<Vec<_>>();
+    let outgoing = |state: &'a Ident| {
+        transitions
+            .iter()
+            .filter(move |transition| hierarchy.is_within(state, transition.initial_state))
+    };
+
+    // A composite state entered through its history resumes a substate the
+    // machine has already been in, so only its initial substate is new
+    let mut reachable = BTreeSet::from([hierarchy.initial_leaf(initial_state)]);
+    let mut pending = vec![hierarchy.initial_leaf(initial_state)];
+    while let Some(state) = pending.pop() {
+        for transition in outgoing(state) {
+            let target = hierarchy.initial_leaf(transition.final_state);
+            if reachable.insert(target) {
+                pending.push(target);
+            }
+        }
+    }
+
+    let unreachable_states = leaves
+        .iter()
+        .filter(|state| !reachable.contains(*state))
+        .map(|state| {
+            (
+                (*state).clone(),
+                Error::new_spanned(
+                    state,
+                    format!("the state `{state}` is unreachable from the initial state"),
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+    // The unreachable states are only reported once
+    let dead_end_states = leaves
+        .iter()
+        .filter(|state| reachable.contains(*state))
+        .filter(|state| outgoing(state).next().is_none())
+        .filter(|state| {
+            !final_states
+                .iter()
+                .any(|final_state| hierarchy.is_within(state, final_state))
+        })
+        .map(|state| {
+            (
+                (*state).clone(),
+                Error::new_spanned(
+                    state,
+                    format!(
+                        "the state `{state}` has no outgoing transitions, but it is not \
+                         declared as final (`#[state_machine(final({state}))]`)"
+                    ),
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut warnings = TokenStream::new();
+    for (name, level, found) in [
+        (
+            "unreachable_states",
+            lints.unreachable_states,
+            unreachable_states,
+        ),
+        (
+            "dead_end_states",
+            lints.dead_end_states.unwrap_or(if final_states.is_empty() {
+                LintLevel::Allow
+            } else {
+                LintLevel::Warn
+            }),
+            dead_end_states,
+        ),
+        (
+            "shadowed_transitions",
+            lints.shadowed_transitions,
+            shadowed_transitions(transitions),
+        ),
+    ] {
+        match level {
+            LintLevel::Allow => {}
+            LintLevel::Deny => {
+                if let Some((_, err)) = found.into_iter().next() {
+                    return Err(err);
+                }
+            }
+            LintLevel::Warn if found.is_empty() => {}
+            LintLevel::Warn => {
+                let module = format_ident!("{name}");
+                // The error displays its first message, pointing at the
+                // problem
+                let constants = found.iter().map(|(constant, err)| {
+                    let message = err.to_string();
+                    quote! {
+                        #[deprecated(note = #message)]
+                        pub const #constant: () = ();
+                    }
+                });
+                let usages = found.iter().map(|(constant, _)| {
+                    quote_spanned! {constant.span()=>
+                        const _: () = #module::#constant;
+                    }
+                });
+                warnings.extend(quote! {
+                    #[doc(hidden)]
+                    #[allow(non_upper_case_globals)]
+                    mod #module {
+                        #(#constants)*
+                    }
+                    #(#usages)*
+                });
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+/// Find the transitions that can never be taken. The match arms are generated
+/// in the order of declaration, so a transition without a guard shadows the
+/// later transitions from the same state with the same input. Every shadowed
+/// state and input is reported once, with a constant named after both of them.
+fn shadowed_transitions(transitions: &[Transition]) -> Vec<(Ident, Error)> {
+    // Point at the input and the new state of a transition, as the state may
+    // be declared far above them in the compact form
+    let transition_tokens = |transition: &Transition| {
+        let input = &transition.input_value.name;
+        let final_state = transition.final_state;
+        quote! { #input #final_state }
+    };
+    let mut found: Vec<(Ident, Error)> = Vec::new();
+    for (i, transition) in transitions.iter().enumerate() {
+        let Some(shadowing) = transitions[..i].iter().find(|earlier| {
+            earlier.guard.is_none()
+                && earlier.initial_state == transition.initial_state
+                && earlier.input_value.name == transition.input_value.name
+        }) else {
+            continue;
+        };
+        let state = transition.initial_state;
+        let input = &transition.input_value.name;
+        let constant = format_ident!("{state}_{input}", span = input.span());
+        if found.iter().any(|(found, _)| *found == constant) {
+            continue;
+        }
+        let message = if transition.guard.is_none() {
+            format!("the transition from `{state}` on `{input}` is declared more than once")
+        } else {
+            format!(
+                "the transition from `{state}` on `{input}` is never taken, as the transition \
+                 without a guard declared before it always matches first"
+            )
+        };
+        let mut err = Error::new_spanned(transition_tokens(transition), message);
+        err.combine(Error::new_spanned(
+            transition_tokens(shadowing),
+            format!("the transition from `{state}` on `{input}` without a guard is declared here"),
+        ));
+        found.push((constant, err));
+    }
+    found
+}
@@ -0,0 +1,172 @@
+use crate::{parser, Hierarchy, Transition};
+
+use quote::ToTokens;
+use std::collections::BTreeMap;
+use syn::{Fields, Ident};
+
+/// Find the equivalent states of a state machine definition by the Moore
+/// partition refinement: two states are equivalent if they carry the same
+/// data, have the same hooks and are nested in the same composite states, and
+/// if their transitions (including the inherited ones) only differ by their
+/// target states, which are equivalent.
+///
+/// The guards, the outputs and the data constructors are compared by their
+/// tokens, so the transitions computing the same thing in different ways are
+/// considered different. The composite states are not compared, as the
+/// current state is always a state without substates.
+///
+/// Returns the groups of the equivalent states, only listing the states having
+/// an equivalent.
+pub fn equivalent_states<'a>(
+    hierarchy: &Hierarchy<'a>,
+    states: &BTreeMap<&'a Ident, &Fields>,
+    definitions: &[parser::TransitionDef],
+    transitions: &[Transition<'a>],
+) -> Vec<Vec<&'a Ident>> {
+    let leaves = states
+        .iter()
+        .filter(|(state, _)| !hierarchy.is_composite(state))
+        .collect::<Vec<_>>();
+    let index = |state: &Ident| leaves.iter().position(|(leaf, _)| **leaf == state);
+
+    // Everything but the target states is compared as text, the targets
+    // entered through the history depend on the past states, so they are only
+    // compared by their names
+    let mut keys = Vec::new();
+    let mut targets = Vec::new();
+    for (state, fields) in &leaves {
+        let mut key = fields.to_token_stream().to_string();
+        for ancestor in hierarchy.ancestors(state) {
+            key.push_str(&format!(" in {ancestor}"));
+        }
+        for def in definitions
+            .iter()
+            .filter(|def| &def.initial_state.name == **state)
+        {
+            for hook in &def.hooks {
+                let kind = match hook.kind {
+                    parser::HookKind::Enter => "on_enter",
+                    parser::HookKind::Exit => "on_exit",
+                    parser::HookKind::Transition => "on_transition",
+                };
+                key.push_str(&format!(" {kind} {};", hook.expr.to_token_stream()));
+            }
+        }
+
+        // The transitions are compared per input, so their declaration order
+        // only matters among the ones sharing the input, which are tried in
+        // that order
+        let mut outgoing = transitions
+            .iter()
+            .filter(|transition| hierarchy.is_within(state, transition.initial_state))
+            .collect::<Vec<_>>();
+        outgoing.sort_by_cached_key(|transition| transition.input_value.name.to_string());
+
+        let mut state_targets = Vec::new();
+        for transition in outgoing {
+            key.push_str(&format!(" {}", transition.input_value.name));
+            if let Some(timeout) = transition.timeout {
+                key.push_str(&format!(" after {}", timeout.millis));
+            }
+            if let Some(guard) = transition.guard {
+                key.push_str(&format!(" if {}", guard.expr.to_token_stream()));
+            }
+            key.push_str(" =>");
+            match transition.history {
+                Some(kind) => {
+                    let kind = match kind {
+                        parser::HistoryKind::Shallow => "H",
+                        parser::HistoryKind::Deep => "H*",
+                    };
+                    key.push_str(&format!(" {}({kind})", transition.final_state));
+                    state_targets.push(None);
+                }
+                None => state_targets.push(index(hierarchy.initial_leaf(transition.final_state))),
+            }
+            if let Some(data) = transition.final_state_data {
+                key.push_str(&format!(" ({})", data.to_token_stream()));
+            }
+            match transition.output {
+                Some(parser::OutputSpec::Constant(output)) => key.push_str(&format!(" [{output}]")),
+                Some(parser::OutputSpec::Call(expr)) => {
+                    key.push_str(&format!(" [{}]", expr.to_token_stream()))
+                }
+                None => {}
+            }
+            key.push(';');
+        }
+        keys.push(key);
+        targets.push(state_targets);
+    }
+
+    // Start with the classes of the states with the same key and split them
+    // until they are stable
+    let mut classes = classify(leaves.len(), |a, b| keys[a] == keys[b]);
+    loop {
+        let refined = classify(leaves.len(), |a, b| {
+            classes[a] == classes[b]
+                && targets[a]
+                    .iter()
+                    .zip(&targets[b])
+                    .all(|targets| match targets {
+                        (Some(target_a), Some(target_b)) => {
+                            classes[*target_a] == classes[*target_b]
+                        }
+                        (target_a, target_b) => target_a == target_b,
+                    })
+        });
+        let stable = refined.iter().max() == classes.iter().max();
+        classes = refined;
+        if stable {
+            break;
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<&Ident>> = BTreeMap::new();
+    for ((state, _), class) in leaves.iter().zip(classes) {
+        groups.entry(class).or_default().push(state);
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+/// Split the states into the classes of the equivalence relation, numbered in
+/// the order of their first states.
+fn classify(count: usize, equivalent: impl Fn(usize, usize) -> bool) -> Vec<usize> {
+    let mut representatives = Vec::new();
+    (0..count)
+        .map(|state| {
+            match representatives
+                .iter()
+                .position(|representative| equivalent(*representative, state))
+            {
+                Some(class) => class,
+                None => {
+                    representatives.push(state);
+                    representatives.len() - 1
+                }
+            }
+        })
+        .collect()
+}
+
+/// The warning listing the equivalent states.
+pub fn warning(groups: &[Vec<&Ident>]) -> String {
+    let groups = groups
+        .iter()
+        .map(|group| {
+            let names = group
+                .iter()
+                .map(|state| format!("`{state}`"))
+                .collect::<Vec<_>>();
+            let (last, rest) = names.split_last().unwrap();
+            format!(
+                "the states {} and {last} are equivalent and can be merged",
+                rest.join(", ")
+            )
+        })
+        .collect::<Vec<_>>();
+    groups.join("; ")
+}
@@ -1,9 +1,12 @@
+use crate::lints::{LintLevel, Lints};
+
 use syn::{
     braced, bracketed, parenthesized,
     parse::{Error, Parse, ParseStream, Result},
     punctuated::Punctuated,
-    token::{Bracket, Paren},
-    Attribute, Expr, Ident, ItemUse, Path, Token, Type, Visibility,
+    token::{Brace, Bracket, Paren},
+    Attribute, Expr, Fields, FieldsNamed, FieldsUnnamed, Ident, ItemUse, LitInt, Path, Token, Type,
+    Visibility,
 };
 
 /// The output of a state transition
@@ -22,8 +25,8 @@ impl Parse for Output {
             let output_content;
             bracketed!(output_content in input);
 
-            // Check if it starts with a closure (|)
-            if output_content.peek(Token![|]) {
+            // Check if it starts with a closure (`|` or `async |`)
+            if output_content.peek(Token![|]) || output_content.peek(Token![async]) {
                 // Parse as closure expression
                 let expr: Expr = output_content.parse()?;
                 return Ok(Self(Some(OutputSpec::Call(expr))));
@@ -66,6 +69,159 @@ impl Parse for InputVariant {
     }
 }
 
+/// Represents a state, which can be a simple identifier or a variant carrying
+/// data (either tuple or struct fields)
+pub struct StateVariant {
+    pub name: Ident,
+    pub fields: Fields,
+}
+
+impl StateVariant {
+    /// Parse the identifier and optionally the data declaration. A
+    /// parenthesized group right after the identifier is only treated as
+    /// tuple fields if it is followed by either another parenthesized group
+    /// (simple format) or by `=> {` (compact format), otherwise it is the
+    /// input of the simple format.
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = input.parse()?;
+        let fields = if input.peek(Brace) {
+            Fields::Named(input.parse::<FieldsNamed>()?)
+        } else if input.peek(Paren) && has_tuple_fields(input) {
+            Fields::Unnamed(input.parse::<FieldsUnnamed>()?)
+        } else {
+            Fields::Unit
+        };
+        Ok(Self { name, fields })
+    }
+}
+
+fn has_tuple_fields(input: ParseStream) -> bool {
+    let fork = input.fork();
+    if fork.parse::<proc_macro2::TokenTree>().is_err() {
+        return false;
+    }
+    fork.peek(Paren) || peek_hook(&fork) || peek_timeout(&fork) || {
+        fork.parse::<Token![=>]>().is_ok() && fork.peek(Brace)
+    }
+}
+
+/// Check if the input continues with a timeout: `=> after(..)`
+fn peek_timeout(input: ParseStream) -> bool {
+    let fork = input.fork();
+    fork.parse::<Token![=>]>().is_ok() && Timeout::peek(&fork)
+}
+
+/// Check if the input continues with a hook: `=> on_enter [..]`
+fn peek_hook(input: ParseStream) -> bool {
+    let fork = input.fork();
+    fork.parse::<Token![=>]>().is_ok() && HookKind::peek(&fork)
+}
+
+/// The moment when a state hook is called
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HookKind {
+    /// `on_enter`: called when the machine enters the state
+    Enter,
+    /// `on_exit`: called when the machine leaves the state
+    Exit,
+    /// `on_transition`: called on every transition from the state
+    Transition,
+}
+
+impl HookKind {
+    fn from_ident(ident: &Ident) -> Option<Self> {
+        if ident == "on_enter" {
+            Some(Self::Enter)
+        } else if ident == "on_exit" {
+            Some(Self::Exit)
+        } else if ident == "on_transition" {
+            Some(Self::Transition)
+        } else {
+            None
+        }
+    }
+
+    fn peek(input: ParseStream) -> bool {
+        input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| Self::from_ident(&ident).is_some())
+    }
+}
+
+/// Represents a hook of a state (e.g., `Open => on_enter [|| start_timer()]`)
+pub struct Hook {
+    pub kind: HookKind,
+    pub expr: Expr,
+}
+
+impl Parse for Hook {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident: Ident = input.parse()?;
+        let kind = HookKind::from_ident(&ident).ok_or_else(|| {
+            Error::new_spanned(&ident, "expected `on_enter`, `on_exit` or `on_transition`")
+        })?;
+        let content;
+        bracketed!(content in input);
+        let expr = content.parse()?;
+        Ok(Self { kind, expr })
+    }
+}
+
+/// Represents a timeout of a state, firing an input once the machine stays in
+/// the state for the given duration (e.g., `after(5s)` or
+/// `after(500ms, TimerTriggered)`). The input is `Timeout` unless specified.
+pub struct Timeout {
+    pub after: Ident,
+    pub millis: u64,
+    pub input: Ident,
+}
+
+impl Timeout {
+    fn peek(input: ParseStream) -> bool {
+        let fork = input.fork();
+        fork.parse::<Ident>().is_ok_and(|ident| ident == "after") && fork.peek(Paren)
+    }
+}
+
+impl Parse for Timeout {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let after: Ident = input.parse()?;
+        let content;
+        parenthesized!(content in input);
+
+        let duration: LitInt = content.parse()?;
+        let value: u64 = duration.base10_parse()?;
+        let unit = match duration.suffix() {
+            "ms" => 1,
+            "s" => 1_000,
+            "min" => 60_000,
+            "h" => 3_600_000,
+            _ => {
+                return Err(Error::new_spanned(
+                    duration,
+                    "expected a duration in `ms`, `s`, `min` or `h` (e.g., `5s`)",
+                ))
+            }
+        };
+        let millis = value
+            .checked_mul(unit)
+            .ok_or_else(|| Error::new_spanned(&duration, "the duration is too long"))?;
+
+        let input = if content.is_empty() {
+            Ident::new("Timeout", after.span())
+        } else {
+            content.parse::<Token![,]>()?;
+            content.parse()?
+        };
+        Ok(Self {
+            after,
+            millis,
+            input,
+        })
+    }
+}
+
 /// Represents a guard expression for a transition
 pub struct Guard {
     pub expr: Expr,
@@ -75,13 +231,92 @@ pub struct Guard {
 /// trait is implemented for the compact form.
 pub struct TransitionEntry {
     pub input_value: InputVariant,
+    /// The timeout firing the input (e.g., `after(5s) => HalfOpen`)
+    pub timeout: Option<Timeout>,
     pub guard: Option<Guard>,
     pub final_state: Ident,
+    /// Builds the data of the final state (e.g., `Failed(|n: &u32| n + 1)`)
+    pub final_state_data: Option<Expr>,
+    /// Enters the final state through its history (e.g., `Active(H)`)
+    pub history: Option<HistoryKind>,
     pub output: Option<OutputSpec>,
 }
 
+/// The history pseudo-state of a composite state entered by a transition
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HistoryKind {
+    /// `H`: resumes the substate the composite state was last in, entering
+    /// a composite substate in its initial substate
+    Shallow,
+    /// `H*`: resumes the substates the composite state was last in, down to
+    /// the innermost one
+    Deep,
+}
+
+impl HistoryKind {
+    fn peek(input: ParseStream) -> bool {
+        let fork = input.fork();
+        if !fork.parse::<Ident>().is_ok_and(|ident| ident == "H") {
+            return false;
+        }
+        if fork.peek(Token![*]) {
+            let _ = fork.parse::<Token![*]>();
+        }
+        fork.is_empty()
+    }
+}
+
+impl Parse for HistoryKind {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Ident>()?;
+        if input.peek(Token![*]) {
+            input.parse::<Token![*]>()?;
+            Ok(Self::Deep)
+        } else {
+            Ok(Self::Shallow)
+        }
+    }
+}
+
+/// Parse the final state and either its optional data constructor or its
+/// history pseudo-state
+fn parse_final_state(input: ParseStream) -> Result<(Ident, Option<Expr>, Option<HistoryKind>)> {
+    let final_state = input.parse()?;
+    if !input.peek(Paren) {
+        return Ok((final_state, None, None));
+    }
+    let content;
+    parenthesized!(content in input);
+    if HistoryKind::peek(&content) {
+        Ok((final_state, None, Some(content.parse()?)))
+    } else {
+        Ok((final_state, Some(content.parse()?), None))
+    }
+}
+
 impl Parse for TransitionEntry {
     fn parse(input: ParseStream) -> Result<Self> {
+        // A timeout fires its input, which carries no data and cannot be
+        // guarded: after(5s) => State
+        if Timeout::peek(input) {
+            let timeout: Timeout = input.parse()?;
+            input.parse::<Token![=>]>()?;
+            let (final_state, final_state_data, history) = parse_final_state(input)?;
+            let output = input.parse::<Output>()?.into();
+            return Ok(Self {
+                input_value: InputVariant {
+                    name: timeout.input.clone(),
+                    fields: Punctuated::new(),
+                },
+                timeout: Some(timeout),
+                guard: None,
+                final_state,
+                final_state_data,
+                history,
+                output,
+            });
+        }
+
         let input_value = InputVariant::parse(input)?;
 
         // Check for optional guard: if <expr>
@@ -95,12 +330,15 @@ impl Parse for TransitionEntry {
         };
 
         input.parse::<Token![=>]>()?;
-        let final_state = input.parse()?;
+        let (final_state, final_state_data, history) = parse_final_state(input)?;
         let output = input.parse::<Output>()?.into();
         Ok(Self {
             input_value,
+            timeout: None,
             guard,
             final_state,
+            final_state_data,
+            history,
             output,
         })
     }
@@ -108,13 +346,16 @@ impl Parse for TransitionEntry {
 
 /// Parses the transition in any of the possible formats.
 pub struct TransitionDef {
-    pub initial_state: Ident,
+    pub initial_state: StateVariant,
     pub transitions: Vec<TransitionEntry>,
+    pub hooks: Vec<Hook>,
+    /// The composite state the transition is declared in
+    pub parent: Option<Ident>,
 }
 
 impl Parse for TransitionDef {
     fn parse(input: ParseStream) -> Result<Self> {
-        let initial_state = input.parse()?;
+        let initial_state = StateVariant::parse(input)?;
         // Parse the transition in the simple format
         // InitialState(Input) => ResultState [Output]
         // Note: Guards are not supported in simple format (only in compact format)
@@ -123,21 +364,41 @@ impl Parse for TransitionDef {
             parenthesized!(input_content in input);
             let input_value = InputVariant::parse(&input_content)?;
             input.parse::<Token![=>]>()?;
-            let final_state = input.parse()?;
+            let (final_state, final_state_data, history) = parse_final_state(input)?;
             let output = input.parse::<Output>()?.into();
 
             vec![TransitionEntry {
                 input_value,
+                timeout: None,
                 guard: None,
                 final_state,
+                final_state_data,
+                history,
                 output,
             }]
+        } else if peek_timeout(input) {
+            // Parse the timeout of the state
+            // State => after(5s) => ResultState [Output]
+            input.parse::<Token![=>]>()?;
+            vec![input.parse()?]
+        } else if peek_hook(input) {
+            // Parse the hook of the state
+            // State => on_enter [|| ..]
+            input.parse::<Token![=>]>()?;
+            return Ok(Self {
+                initial_state,
+                transitions: Vec::new(),
+                hooks: vec![input.parse()?],
+                parent: None,
+            });
         } else {
             // Parse the transition in the compact format
             // InitialState => {
             //     Input1 => State1,
             //     Input2 => State2 [Output]
             // }
+            // A state carrying data may be only declared with an empty block:
+            // InitialState(Data) => {}
             input.parse::<Token![=>]>()?;
             let entries_content;
             braced!(entries_content in input);
@@ -146,9 +407,9 @@ impl Parse for TransitionDef {
                 .parse_terminated(TransitionEntry::parse, Token![,])?
                 .into_iter()
                 .collect();
-            if entries.is_empty() {
+            if entries.is_empty() && matches!(initial_state.fields, Fields::Unit) {
                 return Err(Error::new_spanned(
-                    initial_state,
+                    initial_state.name,
                     "No transitions provided for a compact representation",
                 ));
             }
@@ -157,10 +418,146 @@ impl Parse for TransitionDef {
         Ok(Self {
             initial_state,
             transitions,
+            hooks: Vec::new(),
+            parent: None,
+        })
+    }
+}
+
+/// A composite state with its own substates, declared as the initial substate
+/// followed by the block of the substates:
+///
+/// ```rust,ignore
+/// Connected(Idle) {
+///     Idle(Send) => Busy,
+///     Busy(Done) => Idle,
+/// }
+/// ```
+///
+/// The substates are the states declared in the block, either by their
+/// transitions, or by their names alone (e.g., `Idle(Send) => Busy, Busy`).
+pub struct CompositeState {
+    pub name: Ident,
+    pub initial_state: Ident,
+    /// The composite state this one is nested in
+    pub parent: Option<Ident>,
+}
+
+impl CompositeState {
+    fn peek(input: ParseStream) -> bool {
+        let fork = input.fork();
+        if fork.parse::<Ident>().is_err() {
+            return false;
+        }
+        // Skip the initial substate, which tells the block from the fields of
+        // a state carrying data
+        if !fork.peek(Paren) || fork.parse::<proc_macro2::TokenTree>().is_err() {
+            return false;
+        }
+        fork.peek(Brace)
+    }
+}
+
+/// Parses the states declared at the top level or in the block of a composite
+/// state, flattening the nested composite states
+fn parse_states(
+    input: ParseStream,
+    parent: Option<&Ident>,
+    transitions: &mut Vec<TransitionDef>,
+    composites: &mut Vec<CompositeState>,
+) -> Result<()> {
+    while !input.is_empty() {
+        if peek_region_separator(input) {
+            break;
+        }
+        if CompositeState::peek(input) {
+            let name: Ident = input.parse()?;
+            let initial_state_content;
+            parenthesized!(initial_state_content in input);
+            let initial_state = initial_state_content.parse()?;
+            let states_content;
+            braced!(states_content in input);
+            composites.push(CompositeState {
+                name: name.clone(),
+                initial_state,
+                parent: parent.cloned(),
+            });
+            parse_states(&states_content, Some(&name), transitions, composites)?;
+        } else if parent.is_some() && peek_declaration(input) {
+            // A substate declared by its name alone
+            transitions.push(TransitionDef {
+                initial_state: StateVariant {
+                    name: input.parse()?,
+                    fields: Fields::Unit,
+                },
+                transitions: Vec::new(),
+                hooks: Vec::new(),
+                parent: parent.cloned(),
+            });
+        } else {
+            let mut def: TransitionDef = input.parse()?;
+            def.parent = parent.cloned();
+            transitions.push(def);
+        }
+
+        if input.is_empty() || peek_region_separator(input) {
+            break;
+        }
+        input.parse::<Token![,]>()?;
+    }
+    Ok(())
+}
+
+/// Check if the input continues with the separator of the regions: `--`
+fn peek_region_separator(input: ParseStream) -> bool {
+    input.peek(Token![-]) && input.peek2(Token![-])
+}
+
+/// Represents an orthogonal region of a state machine, a sub-machine
+/// receiving the same inputs as the other regions. The regions are named along
+/// with their initial states and are separated by `--`:
+///
+/// ```rust,ignore
+/// device(power: Awake, link: Offline)
+///
+/// Awake(Sleep) => Asleep,
+/// Asleep(Wake) => Awake,
+/// --
+/// Offline(Connect) => Online,
+/// Online(Disconnect) => Offline,
+/// ```
+pub struct Region {
+    pub name: Ident,
+    pub initial_state: Ident,
+    pub transitions: Vec<TransitionDef>,
+    /// The composite states, in the order of their declaration
+    pub composites: Vec<CompositeState>,
+}
+
+/// Parses a region name along with its initial state: `power: Awake`
+struct RegionHead {
+    name: Ident,
+    initial_state: Ident,
+}
+
+impl Parse for RegionHead {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let initial_state = input.parse()?;
+        Ok(Self {
+            name,
+            initial_state,
         })
     }
 }
 
+/// Check if the state is declared by its name alone
+fn peek_declaration(input: ParseStream) -> bool {
+    let fork = input.fork();
+    fork.parse::<Ident>().is_ok() && (fork.is_empty() || fork.peek(Token![,]))
+}
+
 /// Parses the whole state machine definition in the following form (example):
 ///
 /// ```rust,ignore
@@ -183,10 +580,37 @@ pub struct StateMachineDef {
     pub initial_state: Ident,
     pub use_statements: Vec<ItemUse>,
     pub transitions: Vec<TransitionDef>,
+    /// The composite states, in the order of their declaration
+    pub composites: Vec<CompositeState>,
+    /// The orthogonal regions, if any. The states of a state machine with
+    /// regions are only declared in its regions.
+    pub regions: Vec<Region>,
     pub attributes: Vec<Attribute>,
     pub input_type: Option<Path>,
     pub state_type: Option<Path>,
     pub output_type: Option<Path>,
+    pub context_type: Option<Path>,
+    /// The generated types deriving `Serialize` and `Deserialize`
+    pub serde: SerdeTypes,
+    /// The version of the definition recorded in snapshots
+    pub version: Option<LitInt>,
+    /// Set by `#[state_machine(minimize)]` to report the equivalent states
+    pub minimize: Option<Ident>,
+    /// The states listed in `#[state_machine(final(..))]`, which may have no
+    /// outgoing transitions
+    pub final_states: Vec<Ident>,
+    /// The levels of the lints set with `#[state_machine(deny(..))]` (or
+    /// `allow(..)`, `warn(..)`)
+    pub lints: Lints,
+}
+
+/// The generated types listed in `#[state_machine(serde(state, input, output))]`,
+/// along with the idents used to list them (to report errors)
+#[derive(Default)]
+pub struct SerdeTypes {
+    pub state: Option<Ident>,
+    pub input: Option<Ident>,
+    pub output: Option<Ident>,
 }
 
 impl Parse for StateMachineDef {
@@ -211,11 +635,58 @@ impl Parse for StateMachineDef {
         let mut input_type = None;
         let mut state_type = None;
         let mut output_type = None;
+        let mut context_type = None;
+        let mut serde = SerdeTypes::default();
+        let mut version = None;
+        let mut minimize = None;
+        let mut final_states = Vec::new();
+        let mut lints = Lints::default();
 
         for attribute in state_machine_attributes {
             attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("serde") {
+                    return meta.parse_nested_meta(|meta| {
+                        let ident = meta.path.get_ident().cloned();
+                        if meta.path.is_ident("state") {
+                            serde.state = ident;
+                        } else if meta.path.is_ident("input") {
+                            serde.input = ident;
+                        } else if meta.path.is_ident("output") {
+                            serde.output = ident;
+                        } else {
+                            return Err(meta.error("expected `state`, `input` or `output`"));
+                        }
+                        Ok(())
+                    });
+                }
+
+                if meta.path.is_ident("minimize") {
+                    minimize = meta.path.get_ident().cloned();
+                    return Ok(());
+                }
+
+                for (name, level) in [
+                    ("allow", LintLevel::Allow),
+                    ("warn", LintLevel::Warn),
+                    ("deny", LintLevel::Deny),
+                ] {
+                    if meta.path.is_ident(name) {
+                        return lints.parse(level, meta);
+                    }
+                }
+
                 let content;
                 parenthesized!(content in meta.input);
+                if meta.path.is_ident("final") {
+                    final_states.extend(content.parse_terminated(Ident::parse, Token![,])?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("version") {
+                    let v: LitInt = content.parse()?;
+                    v.base10_parse::<u32>()?;
+                    version = Some(v);
+                    return Ok(());
+                }
                 let p: Path = content.parse()?;
 
                 if meta.path.is_ident("input") {
@@ -224,6 +695,8 @@ impl Parse for StateMachineDef {
                     state_type = Some(p);
                 } else if meta.path.is_ident("output") {
                     output_type = Some(p);
+                } else if meta.path.is_ident("context") {
+                    context_type = Some(p);
                 }
 
                 Ok(())
@@ -235,7 +708,15 @@ impl Parse for StateMachineDef {
 
         let initial_state_content;
         parenthesized!(initial_state_content in input);
-        let initial_state = initial_state_content.parse()?;
+        let region_heads = if initial_state_content.peek2(Token![:]) {
+            initial_state_content.parse_terminated(RegionHead::parse, Token![,])?
+        } else {
+            Punctuated::new()
+        };
+        let initial_state = match region_heads.first() {
+            Some(head) => head.initial_state.clone(),
+            None => initial_state_content.parse()?,
+        };
 
         // Parse optional use statements
         let mut use_statements = Vec::new();
@@ -243,10 +724,39 @@ impl Parse for StateMachineDef {
             use_statements.push(input.parse()?);
         }
 
-        let transitions = input
-            .parse_terminated(TransitionDef::parse, Token![,])?
-            .into_iter()
-            .collect();
+        let mut transitions = Vec::new();
+        let mut composites = Vec::new();
+        let mut regions = Vec::new();
+        if region_heads.is_empty() {
+            parse_states(input, None, &mut transitions, &mut composites)?;
+            if peek_region_separator(input) {
+                return Err(input.error(
+                    "the regions must be named along with their initial states \
+                     (e.g., `device(power: Awake, link: Offline)`)",
+                ));
+            }
+        }
+        for (i, head) in region_heads.into_iter().enumerate() {
+            if i > 0 {
+                if input.is_empty() {
+                    return Err(Error::new_spanned(
+                        &head.name,
+                        format!("the region `{}` is not declared", head.name),
+                    ));
+                }
+                input.parse::<Token![-]>()?;
+                input.parse::<Token![-]>()?;
+            }
+            let mut transitions = Vec::new();
+            let mut composites = Vec::new();
+            parse_states(input, None, &mut transitions, &mut composites)?;
+            regions.push(Region {
+                name: head.name,
+                initial_state: head.initial_state,
+                transitions,
+                composites,
+            });
+        }
 
         Ok(Self {
             doc,
@@ -255,10 +765,18 @@ impl Parse for StateMachineDef {
             initial_state,
             use_statements,
             transitions,
+            composites,
+            regions,
             attributes,
             input_type,
             state_type,
             output_type,
+            context_type,
+            serde,
+            version,
+            minimize,
+            final_states,
+            lints,
         })
     }
 }
@@ -0,0 +1,377 @@
+use crate::{attrs_to_token_stream, fingerprint, is_async_closure, param_names, parser};
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::collections::{BTreeMap, BTreeSet};
+use syn::{parse_quote, punctuated::Punctuated, token::Comma, Error, Ident, Type};
+
+#[cfg(feature = "diagram")]
+use crate::diagram;
+
+/// The parts of the generated region reported to the enclosing state machine
+#[derive(Default)]
+pub struct RegionParts {
+    pub definition_hash: u64,
+    /// The lines of the diagram of the region
+    #[cfg(feature = "diagram")]
+    pub diagram: String,
+}
+
+/// Generate a state machine with orthogonal regions.
+///
+/// Every region is a state machine generated in its own module, named after
+/// the region and sharing the `Input` and `Output` enums of the enclosing
+/// module. The state of the enclosing state machine is the tuple of the states
+/// of the regions, and its output is the array of their outputs. An input is
+/// passed to all regions, the regions without a transition for it keep their
+/// states, so it is only rejected when all regions reject it.
+pub fn generate(input: parser::StateMachineDef) -> TokenStream {
+    if let Some(path) = [&input.input_type, &input.state_type, &input.output_type]
+        .into_iter()
+        .flatten()
+        .next()
+    {
+        return Error::new_spanned(
+            path,
+            "custom types are not supported by state machines with regions",
+        )
+        .to_compile_error();
+    }
+    let mut names = BTreeSet::new();
+    let mut inputs: BTreeMap<&Ident, &Punctuated<Type, Comma>> = BTreeMap::new();
+    let mut outputs = BTreeSet::new();
+    let mut states = BTreeSet::new();
+    for region in &input.regions {
+        states.insert(&region.initial_state);
+        for composite in &region.composites {
+            states.insert(&composite.name);
+            states.insert(&composite.initial_state);
+        }
+        if !names.insert(&region.name) {
+            return Error::new_spanned(
+                &region.name,
+                format!("the region `{}` is declared more than once", region.name),
+            )
+            .to_compile_error();
+        }
+        for def in &region.transitions {
+            states.insert(&def.initial_state.name);
+            if let Some(hook) = def.hooks.first() {
+                return Error::new_spanned(&hook.expr, "hooks are not supported in regions")
+                    .to_compile_error();
+            }
+            for transition in &def.transitions {
+                if let Some(timeout) = &transition.timeout {
+                    return Error::new_spanned(
+                        &timeout.after,
+                        "timeouts are not supported in regions",
+                    )
+                    .to_compile_error();
+                }
+                if transition.history.is_some() {
+                    return Error::new_spanned(
+                        &transition.final_state,
+                        "history is not supported in regions",
+                    )
+                    .to_compile_error();
+                }
+                let async_closure = [
+                    transition.guard.as_ref().map(|guard| &guard.expr),
+                    transition.final_state_data.as_ref(),
+                    match &transition.output {
+                        Some(parser::OutputSpec::Call(expr)) => Some(expr),
+                        _ => None,
+                    },
+                ]
+                .into_iter()
+                .flatten()
+                .find(|expr| is_async_closure(expr));
+                if let Some(expr) = async_closure {
+                    return Error::new_spanned(
+                        expr,
+                        "regions are not supported by asynchronous state machines",
+                    )
+                    .to_compile_error();
+                }
+
+                states.insert(&transition.final_state);
+                inputs
+                    .entry(&transition.input_value.name)
+                    .or_insert(&transition.input_value.fields);
+                if let Some(parser::OutputSpec::Constant(output)) = &transition.output {
+                    outputs.insert(output);
+                }
+            }
+        }
+    }
+
+    // The final states are passed to all regions, so each of them is only
+    // required to be declared in one of the regions
+    if let Some(state) = input
+        .final_states
+        .iter()
+        .find(|state| !states.contains(state))
+    {
+        return Error::new_spanned(state, format!("the state `{state}` is not declared"))
+            .to_compile_error();
+    }
+
+    // The input and the output enums are shared by all regions
+    let attrs = attrs_to_token_stream(input.attributes.clone());
+    let impl_attrs = attrs_to_token_stream(
+        input
+            .attributes
+            .iter()
+            .filter(|attr| !attr.path().is_ident("repr"))
+            .cloned()
+            .collect(),
+    );
+    let input_serde = crate::serde_derive(&input.serde.input);
+    let output_serde = crate::serde_derive(&input.serde.output);
+    let input_variants = inputs.iter().map(|(name, fields)| {
+        if !fields.is_empty() {
+            quote! { #[allow(unused)] #name(#fields) }
+        } else {
+            quote! { #[allow(unused)] #name }
+        }
+    });
+    let input_impl = quote! {
+        #attrs
+        #input_serde
+        pub enum Input {
+            #(#input_variants),*
+        }
+    };
+    // Skip the attributes of an empty output alphabet, as in the state
+    // machines without regions
+    let output_attrs = if outputs.is_empty() { quote!() } else { attrs };
+    let output_impl = quote! {
+        #output_attrs
+        #output_serde
+        pub enum Output {
+            #(#outputs),*
+        }
+    };
+
+    let parser::StateMachineDef {
+        doc,
+        visibility,
+        name: fsm_name,
+        use_statements,
+        regions,
+        attributes,
+        context_type,
+        serde,
+        version,
+        minimize,
+        final_states,
+        lints,
+        ..
+    } = input;
+    let doc = attrs_to_token_stream(doc);
+
+    let mut region_names = Vec::new();
+    let mut region_modules = Vec::new();
+    let mut region_hashes = Vec::new();
+    #[cfg(feature = "diagram")]
+    let mut region_diagrams = Vec::new();
+    for region in regions {
+        let def = parser::StateMachineDef {
+            doc: Vec::new(),
+            visibility: parse_quote!(pub),
+            name: region.name.clone(),
+            initial_state: region.initial_state,
+            use_statements: vec![parse_quote!(
+                use super::*;
+            )],
+            transitions: region.transitions,
+            composites: region.composites,
+            regions: Vec::new(),
+            attributes: attributes.clone(),
+            input_type: None,
+            state_type: None,
+            output_type: None,
+            context_type: context_type.clone(),
+            serde: parser::SerdeTypes {
+                state: serde.state.clone(),
+                input: None,
+                output: None,
+            },
+            version: version.clone(),
+            minimize: minimize.clone(),
+            final_states: final_states.clone(),
+            lints: lints.clone(),
+        };
+        let mut parts = RegionParts::default();
+        region_modules.push(crate::generate(def, Some(&mut parts)));
+        region_hashes.push(parts.definition_hash);
+        #[cfg(feature = "diagram")]
+        region_diagrams.push(parts.diagram);
+        region_names.push(region.name);
+    }
+
+    let definition_hash =
+        fingerprint::regions_hash(&region_names.iter().zip(region_hashes).collect::<Vec<_>>());
+
+    #[cfg(feature = "diagram")]
+    let diagram = diagram::render_regions(&fsm_name, &region_diagrams);
+    #[cfg(not(feature = "diagram"))]
+    let diagram = quote!();
+
+    // The regions are driven through `StateMachineContextImpl`, which is also
+    // implemented by the regions without a context
+    let count = region_names.len();
+    let impls = region_names
+        .iter()
+        .map(|name| quote! { <#name::Impl as ::rust_fsm::StateMachineContextImpl> })
+        .collect::<Vec<_>>();
+    let states = param_names("__state", count);
+    let steps = param_names("__step", count);
+    let next_states = param_names("__next", count);
+    let outputs = param_names("__output", count);
+    let (context, context_mut) = if context_type.is_some() {
+        (quote!(context), quote!(context))
+    } else {
+        (quote!(&()), quote!(&mut ()))
+    };
+
+    let transition_body = quote! {
+        let (#(#states,)*) = state;
+        #(let #next_states = #impls::transition_with(#states, input, #context);)*
+        if #(#next_states.is_none())&&* {
+            return None;
+        }
+        Some((#(#next_states.unwrap_or_else(|| ::core::clone::Clone::clone(#states)),)*))
+    };
+    let output_body = quote! {
+        let (#(#states,)*) = state;
+        let outputs = [#(#impls::output_with(#states, input, #context_mut)),*];
+        outputs.iter().any(Option::is_some).then_some(outputs)
+    };
+    let step_body = quote! {
+        let (#(#states,)*) = state;
+        #(let #steps = #impls::step_with(#states, input, #context_mut);)*
+        if #(#steps.is_none())&&* {
+            return None;
+        }
+        #(let (#next_states, #outputs) = match #steps {
+            Some(step) => step,
+            None => (::core::clone::Clone::clone(#states), None),
+        };)*
+        let outputs = [#(#outputs),*];
+        Some((
+            (#(#next_states,)*),
+            outputs.iter().any(Option::is_some).then_some(outputs),
+        ))
+    };
+    // All regions rejected the input, the guards rejecting it in any region
+    // explain the rejection
+    let rejection_body = quote! {
+        let (#(#states,)*) = state;
+        [#(#impls::rejection_reason_with(#states, input, #context)),*]
+            .into_iter()
+            .find(|reason| *reason != ::rust_fsm::RejectionReason::NoTransition)
+            .unwrap_or(::rust_fsm::RejectionReason::NoTransition)
+    };
+
+    let state_machine_impl = match context_type {
+        Some(context_type) => quote! {
+            impl ::rust_fsm::StateMachineContextImpl for Impl {
+                type Input = Input;
+                type State = State;
+                type Output = [Option<Output>; #count];
+                type Context = #context_type;
+                const INITIAL_STATE: Self::State = (#(#impls::INITIAL_STATE,)*);
+
+                fn transition_with(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    context: &Self::Context,
+                ) -> Option<Self::State> {
+                    #transition_body
+                }
+
+                fn output_with(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    context: &mut Self::Context,
+                ) -> Option<Self::Output> {
+                    #output_body
+                }
+
+                fn step_with(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    context: &mut Self::Context,
+                ) -> Option<(Self::State, Option<Self::Output>)> {
+                    #step_body
+                }
+
+                fn rejection_reason_with(
+                    state: &Self::State,
+                    input: &Self::Input,
+                    context: &Self::Context,
+                ) -> ::rust_fsm::RejectionReason {
+                    #rejection_body
+                }
+            }
+        },
+        None => quote! {
+            impl ::rust_fsm::StateMachineImpl for Impl {
+                type Input = Input;
+                type State = State;
+                type Output = [Option<Output>; #count];
+                const INITIAL_STATE: Self::State = (#(#impls::INITIAL_STATE,)*);
+
+                fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
+                    #transition_body
+                }
+
+                fn output(state: &Self::State, input: &Self::Input) -> Option<Self::Output> {
+                    #output_body
+                }
+
+                fn step(
+                    state: &Self::State,
+                    input: &Self::Input,
+                ) -> Option<(Self::State, Option<Self::Output>)> {
+                    #step_body
+                }
+
+                fn rejection_reason(
+                    state: &Self::State,
+                    input: &Self::Input,
+                ) -> ::rust_fsm::RejectionReason {
+                    #rejection_body
+                }
+            }
+        },
+    };
+
+    quote! {
+        #doc
+        #diagram
+        #visibility mod #fsm_name {
+            #(#use_statements)*
+
+            /// The fingerprint of the state machine definition, which changes
+            /// whenever its states, inputs, outputs or transitions change.
+            pub const DEFINITION_HASH: u64 = #definition_hash;
+
+            #impl_attrs
+            pub struct Impl;
+
+            pub type StateMachine = ::rust_fsm::StateMachine<Impl>;
+
+            /// The states of the regions
+            pub type State = (#(#region_names::State,)*);
+
+            #input_impl
+            #output_impl
+
+            #(#region_modules)*
+
+            #state_machine_impl
+        }
+    }
+}
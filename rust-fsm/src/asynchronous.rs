@@ -0,0 +1,159 @@
+use crate::{RejectionReason, TransitionImpossibleError, TransitionRejectedError};
+use core::future::Future;
+
+/// The same as [`StateMachineContextImpl`](crate::StateMachineContextImpl),
+/// but the single-step function is asynchronous, so it can await guards and
+/// outputs, e.g. querying a cache or a database. The futures are not bound to
+/// any particular executor.
+///
+/// The rejection reason and the hooks stay synchronous.
+pub trait AsyncStateMachineImpl {
+    /// The input alphabet.
+    type Input;
+    /// The set of possible states.
+    type State;
+    /// The output alphabet.
+    type Output;
+    /// The context passed to the step function and the hooks. Use `()` when
+    /// the machine does not need any context.
+    type Context;
+    /// The initial state of the machine.
+    // allow since there is usually no interior mutability because states are enums
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INITIAL_STATE: Self::State;
+    /// Resolves to the new state along with the output based on the current
+    /// state, the provided input and the context, or to `None` when there is
+    /// no transition for a given combination of the input and the state.
+    fn step(
+        state: &Self::State,
+        input: &Self::Input,
+        context: &mut Self::Context,
+    ) -> impl Future<Output = Option<(Self::State, Option<Self::Output>)>>;
+    /// The same as [`StateMachineImpl::rejection_reason`](crate::StateMachineImpl::rejection_reason),
+    /// but with the context.
+    fn rejection_reason(
+        _state: &Self::State,
+        _input: &Self::Input,
+        _context: &Self::Context,
+    ) -> RejectionReason {
+        RejectionReason::NoTransition
+    }
+    /// The same as [`StateMachineImpl::on_exit`](crate::StateMachineImpl::on_exit),
+    /// but with the context.
+    fn on_exit(_state: &Self::State, _context: &mut Self::Context) {}
+    /// The same as [`StateMachineImpl::on_transition`](crate::StateMachineImpl::on_transition),
+    /// but with the context.
+    fn on_transition(
+        _from: &Self::State,
+        _input: &Self::Input,
+        _to: &Self::State,
+        _context: &mut Self::Context,
+    ) {
+    }
+    /// The same as [`StateMachineImpl::on_enter`](crate::StateMachineImpl::on_enter),
+    /// but with the context.
+    fn on_enter(_state: &Self::State, _context: &mut Self::Context) {}
+}
+
+/// The same as [`StateMachine`](crate::StateMachine), but for
+/// [`AsyncStateMachineImpl`]: consuming an input is asynchronous.
+#[derive(Debug, Clone)]
+pub struct AsyncStateMachine<T: AsyncStateMachineImpl> {
+    state: T::State,
+}
+
+impl<T> AsyncStateMachine<T>
+where
+    T: AsyncStateMachineImpl,
+{
+    /// Create a new instance of this wrapper which encapsulates the initial
+    /// state.
+    pub fn new() -> Self {
+        Self::from_state(T::INITIAL_STATE)
+    }
+
+    /// Create a new instance of this wrapper which encapsulates the given
+    /// state.
+    pub fn from_state(state: T::State) -> Self {
+        Self { state }
+    }
+
+    /// Consumes the provided input, gives an output and performs a state
+    /// transition using the provided context. If a state transition with the
+    /// current state, the provided input and the context is not allowed,
+    /// returns an error.
+    ///
+    /// The hooks are called in the same order as in
+    /// [`StateMachine::consume_with`](crate::StateMachine::consume_with).
+    pub async fn consume_with(
+        &mut self,
+        context: &mut T::Context,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        self.try_consume_with(context, input)
+            .await
+            .map_err(TransitionImpossibleError::from)
+    }
+
+    /// The same as [`AsyncStateMachine::consume_with`], but the error carries
+    /// references to the current state and the rejected input.
+    #[allow(clippy::type_complexity)]
+    pub async fn try_consume_with<'a>(
+        &'a mut self,
+        context: &mut T::Context,
+        input: &'a T::Input,
+    ) -> Result<Option<T::Output>, TransitionRejectedError<&'a T::State, &'a T::Input>> {
+        if let Some((state, output)) = T::step(&self.state, input, context).await {
+            T::on_exit(&self.state, context);
+            T::on_transition(&self.state, input, &state, context);
+            self.state = state;
+            T::on_enter(&self.state, context);
+            Ok(output)
+        } else {
+            Err(TransitionRejectedError {
+                state: &self.state,
+                input,
+                reason: T::rejection_reason(&self.state, input, context),
+            })
+        }
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> &T::State {
+        &self.state
+    }
+}
+
+impl<T> AsyncStateMachine<T>
+where
+    T: AsyncStateMachineImpl<Context = ()>,
+{
+    /// Consumes the provided input, gives an output and performs a state
+    /// transition. If a state transition with the current state and the
+    /// provided input is not allowed, returns an error.
+    pub async fn consume(
+        &mut self,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        self.consume_with(&mut (), input).await
+    }
+
+    /// The same as [`AsyncStateMachine::consume`], but the error carries
+    /// references to the current state and the rejected input.
+    #[allow(clippy::type_complexity)]
+    pub async fn try_consume<'a>(
+        &'a mut self,
+        input: &'a T::Input,
+    ) -> Result<Option<T::Output>, TransitionRejectedError<&'a T::State, &'a T::Input>> {
+        self.try_consume_with(&mut (), input).await
+    }
+}
+
+impl<T> Default for AsyncStateMachine<T>
+where
+    T: AsyncStateMachineImpl,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{StateMachineImpl, TransitionImpossibleError};
+use core::{
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+/// A state that can be stored in an [`AtomicU8`], e.g. a fieldless
+/// `#[repr(u8)]` enum. The DSL implements this trait for the generated `State`
+/// enum when all states are fieldless and the `#[repr(u8)]` attribute is set.
+pub trait AtomicState: Sized {
+    /// Converts the state into its discriminant.
+    fn to_u8(&self) -> u8;
+    /// Converts a discriminant back into the state. Returns `None` if no state
+    /// has the given discriminant.
+    fn from_u8(value: u8) -> Option<Self>;
+}
+
+/// A lock-free alternative to [`SharedStateMachine`](crate::SharedStateMachine)
+/// for states implementing [`AtomicState`]. The state is stored in an
+/// [`AtomicU8`] and is replaced with a compare-exchange loop over
+/// [`StateMachineImpl::transition`], which is called once per attempt. When
+/// other threads change the state concurrently, the attempt is retried, so the
+/// guards may run again. Once the state is replaced, the output function is
+/// called once, then the hooks, in the same order as by
+/// [`StateMachine`](crate::StateMachine). The outputs and the hooks of the
+/// transitions performed concurrently by several threads may run in any order.
+///
+/// Only the state is stored, so the state machines entering composite states
+/// through their history are not supported: the DSL implements
+/// [`AtomicState`] for flat state machines only.
+pub struct AtomicStateMachine<T: StateMachineImpl> {
+    state: AtomicU8,
+    retries: AtomicUsize,
+    _impl: PhantomData<fn() -> T>,
+}
+
+impl<T> AtomicStateMachine<T>
+where
+    T: StateMachineImpl,
+    T::State: AtomicState,
+{
+    /// Create a new instance of this wrapper which encapsulates the initial
+    /// state.
+    pub fn new() -> Self {
+        Self::from_state(T::INITIAL_STATE)
+    }
+
+    /// Create a new instance of this wrapper which encapsulates the given
+    /// state.
+    pub fn from_state(state: T::State) -> Self {
+        Self {
+            state: AtomicU8::new(state.to_u8()),
+            retries: AtomicUsize::new(0),
+            _impl: PhantomData,
+        }
+    }
+
+    /// Consumes the provided input, gives an output and performs a state
+    /// transition. If a state transition with the current state and the
+    /// provided input is not allowed, returns an error.
+    pub fn consume(
+        &self,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        let mut current = self.state.load(Ordering::Acquire);
+        loop {
+            let state = Self::decode(current);
+            let new_state = T::transition(&state, input).ok_or(TransitionImpossibleError)?;
+            match self.state.compare_exchange(
+                current,
+                new_state.to_u8(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let output = T::output(&state, input);
+                    T::on_exit(&state);
+                    T::on_transition(&state, input, &new_state);
+                    T::on_enter(&new_state);
+                    return Ok(output);
+                }
+                Err(actual) => {
+                    self.retries.fetch_add(1, Ordering::Relaxed);
+                    current = actual;
+                }
+            }
+        }
+    }
+
+    /// Returns a snapshot of the current state.
+    pub fn state(&self) -> T::State {
+        Self::decode(self.state.load(Ordering::Acquire))
+    }
+
+    /// Returns how many times a transition had to be retried because another
+    /// thread changed the state concurrently, since the machine was created.
+    pub fn retries(&self) -> usize {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    fn decode(value: u8) -> T::State {
+        T::State::from_u8(value).expect("the atomic state machine stores a valid state")
+    }
+}
+
+impl<T> Default for AtomicStateMachine<T>
+where
+    T: StateMachineImpl,
+    T::State: AtomicState,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for AtomicStateMachine<T>
+where
+    T: StateMachineImpl,
+    T::State: AtomicState + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AtomicStateMachine")
+            .field("state", &self.state())
+            .field("retries", &self.retries())
+            .finish()
+    }
+}
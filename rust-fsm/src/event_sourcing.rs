@@ -0,0 +1,274 @@
+use crate::{
+    History, NoHistory, NoRecorder, RejectionReason, StateMachine, StateMachineContextImpl,
+    TransitionImpossibleError, TransitionRecorder, TransitionRejectedError,
+};
+use core::{borrow::Borrow, fmt};
+
+/// An append-only log of the inputs accepted by an
+/// [`EventSourcedStateMachine`], e.g. a table of the events of an order.
+pub trait EventLog<I> {
+    /// Called with every accepted input, after the state transition.
+    fn append(&mut self, input: &I);
+}
+
+#[cfg(feature = "std")]
+impl<I> EventLog<I> for Vec<I>
+where
+    I: Clone,
+{
+    fn append(&mut self, input: &I) {
+        self.push(input.clone());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An error returned when an input of a replayed log is rejected, usually
+/// because the definition of the state machine has changed since the log was
+/// written.
+pub struct ReplayError<S, I> {
+    /// The position of the rejected input in the log.
+    pub index: usize,
+    /// The state reached by replaying the preceding inputs.
+    pub state: S,
+    /// The rejected input.
+    pub input: I,
+    /// Why the input was rejected.
+    pub reason: RejectionReason,
+}
+
+impl<T, R, H> StateMachine<T, R, H>
+where
+    T: StateMachineContextImpl,
+    R: TransitionRecorder<T> + Default,
+    H: History<T> + Default,
+{
+    /// Rebuilds the state machine by stepping it with the inputs, starting
+    /// from the initial state, using the provided context. The outputs are
+    /// computed, so the changes they make to the context are replayed too, and
+    /// then discarded. The hooks are not called and nothing is recorded. The
+    /// history is rebuilt along with the state. Returns an error on the first
+    /// input that is rejected.
+    ///
+    /// Note that the replay reproduces the state and the context only if the
+    /// context starts as the one the inputs were first consumed with, and if
+    /// the hooks do not modify it.
+    pub fn replay_with<I>(
+        context: &mut T::Context,
+        inputs: impl IntoIterator<Item = I>,
+    ) -> Result<Self, ReplayError<T::State, I>>
+    where
+        I: Borrow<T::Input>,
+    {
+        let mut state = T::INITIAL_STATE;
+        let mut history = H::default();
+        for (index, input) in inputs.into_iter().enumerate() {
+            history.remember(&state);
+            match history.step_with(&state, input.borrow(), context) {
+                Some((new_state, _)) => state = new_state,
+                None => {
+                    let reason = T::rejection_reason_with(&state, input.borrow(), context);
+                    return Err(ReplayError {
+                        index,
+                        state,
+                        input,
+                        reason,
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            state,
+            recorder: R::default(),
+            history,
+        })
+    }
+}
+
+impl<T, R, H> StateMachine<T, R, H>
+where
+    T: StateMachineContextImpl<Context = ()>,
+    R: TransitionRecorder<T> + Default,
+    H: History<T> + Default,
+{
+    /// The same as [`StateMachine::replay_with`], but without a context.
+    pub fn replay<I>(inputs: impl IntoIterator<Item = I>) -> Result<Self, ReplayError<T::State, I>>
+    where
+        I: Borrow<T::Input>,
+    {
+        Self::replay_with(&mut (), inputs)
+    }
+}
+
+/// A wrapper around [`StateMachine`] appending every accepted input to an
+/// [`EventLog`]. The state is not stored anywhere: it is rebuilt from the log
+/// with [`EventSourcedStateMachine::restore`], e.g. after a restart.
+///
+/// The history `H` of the wrapped [`StateMachine`] must be set for the state
+/// machines entering composite states through their history (e.g.,
+/// `EventSourcedStateMachine<player::Impl, Vec<player::Input>, SubstateHistory<player::Impl>>`),
+/// it is rebuilt along with the state.
+pub struct EventSourcedStateMachine<T: StateMachineContextImpl, L, H = NoHistory> {
+    machine: StateMachine<T, NoRecorder, H>,
+    log: L,
+}
+
+impl<T, L, H> EventSourcedStateMachine<T, L, H>
+where
+    T: StateMachineContextImpl,
+    L: EventLog<T::Input>,
+    H: History<T> + Default,
+{
+    /// Create a new instance of this wrapper which encapsulates the initial
+    /// state and appends to the given log, which is expected to be empty.
+    pub fn new(log: L) -> Self {
+        Self {
+            machine: StateMachine::new(),
+            log,
+        }
+    }
+
+    /// Create a new instance of this wrapper by replaying the given log using
+    /// the provided context (see [`StateMachine::replay_with`]). The accepted
+    /// inputs are appended to the same log.
+    pub fn restore_with(
+        context: &mut T::Context,
+        log: L,
+    ) -> Result<Self, ReplayError<T::State, T::Input>>
+    where
+        for<'a> &'a L: IntoIterator<Item = &'a T::Input>,
+        T::Input: Clone,
+    {
+        let machine = StateMachine::replay_with(context, &log).map_err(ReplayError::cloned)?;
+        Ok(Self { machine, log })
+    }
+
+    /// The same as [`StateMachine::consume_with`]. Appends the input to the
+    /// log if it is accepted.
+    pub fn consume_with(
+        &mut self,
+        context: &mut T::Context,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        self.try_consume_with(context, input)
+            .map_err(TransitionImpossibleError::from)
+    }
+
+    /// The same as [`StateMachine::try_consume_with`]. Appends the input to
+    /// the log if it is accepted.
+    #[allow(clippy::type_complexity)]
+    pub fn try_consume_with<'a>(
+        &'a mut self,
+        context: &mut T::Context,
+        input: &'a T::Input,
+    ) -> Result<Option<T::Output>, TransitionRejectedError<&'a T::State, &'a T::Input>> {
+        let output = self.machine.try_consume_with(context, input)?;
+        self.log.append(input);
+        Ok(output)
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> &T::State {
+        self.machine.state()
+    }
+
+    /// Returns the log of the accepted inputs.
+    pub fn log(&self) -> &L {
+        &self.log
+    }
+
+    /// Returns the wrapped state machine and the log.
+    pub fn into_parts(self) -> (StateMachine<T, NoRecorder, H>, L) {
+        (self.machine, self.log)
+    }
+}
+
+impl<T, L, H> EventSourcedStateMachine<T, L, H>
+where
+    T: StateMachineContextImpl<Context = ()>,
+    L: EventLog<T::Input>,
+    H: History<T> + Default,
+{
+    /// The same as [`EventSourcedStateMachine::restore_with`], but without a
+    /// context.
+    pub fn restore(log: L) -> Result<Self, ReplayError<T::State, T::Input>>
+    where
+        for<'a> &'a L: IntoIterator<Item = &'a T::Input>,
+        T::Input: Clone,
+    {
+        Self::restore_with(&mut (), log)
+    }
+
+    /// The same as [`StateMachine::consume`]. Appends the input to the log if
+    /// it is accepted.
+    pub fn consume(
+        &mut self,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        self.consume_with(&mut (), input)
+    }
+
+    /// The same as [`StateMachine::try_consume`]. Appends the input to the
+    /// log if it is accepted.
+    #[allow(clippy::type_complexity)]
+    pub fn try_consume<'a>(
+        &'a mut self,
+        input: &'a T::Input,
+    ) -> Result<Option<T::Output>, TransitionRejectedError<&'a T::State, &'a T::Input>> {
+        self.try_consume_with(&mut (), input)
+    }
+}
+
+impl<T, L, H> fmt::Debug for EventSourcedStateMachine<T, L, H>
+where
+    T: StateMachineContextImpl,
+    H: History<T>,
+    T::State: fmt::Debug,
+    L: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventSourcedStateMachine")
+            .field("state", self.machine.state())
+            .field("log", &self.log)
+            .finish()
+    }
+}
+
+impl<S, I> ReplayError<S, &I>
+where
+    I: Clone,
+{
+    /// Clones the input so the error no longer borrows the log.
+    pub fn cloned(self) -> ReplayError<S, I> {
+        ReplayError {
+            index: self.index,
+            state: self.state,
+            input: self.input.clone(),
+            reason: self.reason,
+        }
+    }
+}
+
+impl<S, I> fmt::Display for ReplayError<S, I>
+where
+    S: fmt::Debug,
+    I: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot replay the input {:?} at {} from the state {:?}: {}",
+            self.input, self.index, self.state, self.reason
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S, I> std::error::Error for ReplayError<S, I>
+where
+    S: fmt::Debug,
+    I: fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
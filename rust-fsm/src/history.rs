@@ -0,0 +1,205 @@
+use crate::StateMachineContextImpl;
+use core::fmt;
+
+/// The history of the composite states of a hierarchical state machine, i.e.
+/// the substates they were last in. It is implemented by DSL for the state
+/// machines with transitions entering composite states through their history
+/// (`=> Active(H)` or `=> Active(H*)`).
+///
+/// The transition functions of [`StateMachineContextImpl`] enter these
+/// composite states in their initial substates, the functions of this trait
+/// resume the remembered substates instead.
+pub trait HistoryImpl: StateMachineContextImpl {
+    /// The substates the composite states were last in.
+    type History: Default;
+
+    /// Remembers the substates of the composite states containing the state.
+    fn remember(history: &mut Self::History, state: &Self::State);
+
+    /// The same as [`StateMachineContextImpl::transition_with`], but resuming
+    /// the remembered substates of the composite states entered through their
+    /// history.
+    fn transition_with_history(
+        state: &Self::State,
+        input: &Self::Input,
+        context: &Self::Context,
+        history: &Self::History,
+    ) -> Option<Self::State>;
+
+    /// The same as [`StateMachineContextImpl::step_with`], but resuming the
+    /// remembered substates of the composite states entered through their
+    /// history.
+    fn step_with_history(
+        state: &Self::State,
+        input: &Self::Input,
+        context: &mut Self::Context,
+        history: &Self::History,
+    ) -> Option<(Self::State, Option<Self::Output>)>;
+}
+
+/// The history kept by a [`StateMachine`](crate::StateMachine) to compute its
+/// transitions.
+pub trait History<T: StateMachineContextImpl> {
+    /// Called with the current state before every input is consumed.
+    fn remember(&mut self, state: &T::State);
+
+    /// Computes the new state (see [`StateMachineContextImpl::transition_with`]).
+    fn transition_with(
+        &self,
+        state: &T::State,
+        input: &T::Input,
+        context: &T::Context,
+    ) -> Option<T::State>;
+
+    /// Computes the new state and the output at once (see
+    /// [`StateMachineContextImpl::step_with`]).
+    fn step_with(
+        &self,
+        state: &T::State,
+        input: &T::Input,
+        context: &mut T::Context,
+    ) -> Option<(T::State, Option<T::Output>)>;
+
+    /// Whether the history never remembers anything, so it is not serialized
+    /// along with the state machine. Returns `false` by default.
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// The history that remembers nothing, so the composite states are always
+/// entered in their initial substates. This is the default history of
+/// [`StateMachine`](crate::StateMachine).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoHistory;
+
+impl<T> History<T> for NoHistory
+where
+    T: StateMachineContextImpl,
+{
+    fn remember(&mut self, _: &T::State) {}
+
+    fn transition_with(
+        &self,
+        state: &T::State,
+        input: &T::Input,
+        context: &T::Context,
+    ) -> Option<T::State> {
+        T::transition_with(state, input, context)
+    }
+
+    fn step_with(
+        &self,
+        state: &T::State,
+        input: &T::Input,
+        context: &mut T::Context,
+    ) -> Option<(T::State, Option<T::Output>)> {
+        T::step_with(state, input, context)
+    }
+
+    fn is_empty(&self) -> bool {
+        true
+    }
+}
+
+/// The history of the state machines implementing [`HistoryImpl`], resuming
+/// the substates the composite states were last in. DSL uses it in the
+/// generated `StateMachine` type when needed.
+///
+/// With the `serde` feature, it is serialized as the remembered substates.
+pub struct SubstateHistory<T: HistoryImpl>(T::History);
+
+impl<T> SubstateHistory<T>
+where
+    T: HistoryImpl,
+{
+    /// Returns the remembered substates.
+    pub fn get(&self) -> &T::History {
+        &self.0
+    }
+
+    /// Forgets the remembered substates, so the composite states are entered
+    /// in their initial substates until they are left again.
+    pub fn clear(&mut self) {
+        self.0 = T::History::default();
+    }
+}
+
+impl<T> History<T> for SubstateHistory<T>
+where
+    T: HistoryImpl,
+{
+    fn remember(&mut self, state: &T::State) {
+        T::remember(&mut self.0, state);
+    }
+
+    fn transition_with(
+        &self,
+        state: &T::State,
+        input: &T::Input,
+        context: &T::Context,
+    ) -> Option<T::State> {
+        T::transition_with_history(state, input, context, &self.0)
+    }
+
+    fn step_with(
+        &self,
+        state: &T::State,
+        input: &T::Input,
+        context: &mut T::Context,
+    ) -> Option<(T::State, Option<T::Output>)> {
+        T::step_with_history(state, input, context, &self.0)
+    }
+}
+
+impl<T> Default for SubstateHistory<T>
+where
+    T: HistoryImpl,
+{
+    fn default() -> Self {
+        Self(T::History::default())
+    }
+}
+
+impl<T> Clone for SubstateHistory<T>
+where
+    T: HistoryImpl,
+    T::History: Clone,
+{
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> fmt::Debug for SubstateHistory<T>
+where
+    T: HistoryImpl,
+    T::History: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SubstateHistory").field(&self.0).finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for SubstateHistory<T>
+where
+    T: HistoryImpl,
+    T::History: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for SubstateHistory<T>
+where
+    T: HistoryImpl,
+    T::History: serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::History::deserialize(deserializer).map(Self)
+    }
+}
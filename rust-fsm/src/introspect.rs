@@ -0,0 +1,166 @@
+use crate::StateMachineImpl;
+
+/// A state machine with finite sets of states and inputs that can be listed,
+/// so its definition can be analyzed. DSL implements it for the state machines
+/// without a context, whose states and inputs carry no data.
+pub trait IntrospectImpl: StateMachineImpl<State: 'static, Input: 'static> {
+    /// All states of the state machine.
+    const STATES: &'static [Self::State];
+    /// All inputs of the state machine.
+    const INPUTS: &'static [Self::Input];
+}
+
+/// Finds the states that can be merged without changing the behavior of the
+/// state machine by the partition refinement: two states are equivalent if
+/// every input is either rejected in both of them, or produces the same output
+/// and leads to equivalent states.
+///
+/// Returns the groups of the equivalent states, in the order of
+/// [`IntrospectImpl::STATES`], only listing the states having an equivalent.
+///
+/// # Panics
+///
+/// Panics if a transition leads to a state not listed in
+/// [`IntrospectImpl::STATES`].
+#[cfg(feature = "std")]
+pub fn equivalent_states<T>() -> Vec<Vec<T::State>>
+where
+    T: IntrospectImpl,
+    T::State: Clone + PartialEq,
+    T::Output: PartialEq,
+{
+    let states = T::STATES;
+    let index = |state: &T::State| {
+        states
+            .iter()
+            .position(|known| known == state)
+            .expect("all states must be listed in `IntrospectImpl::STATES`")
+    };
+    let steps = states
+        .iter()
+        .map(|state| {
+            T::INPUTS
+                .iter()
+                .map(|input| T::step(state, input).map(|(next, output)| (index(&next), output)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // Start with a single class and split the classes until they are stable.
+    // The first split separates the states by their outputs.
+    let mut classes = vec![0; states.len()];
+    let mut count = 1;
+    loop {
+        let equivalent = |a: usize, b: usize| {
+            classes[a] == classes[b]
+                && steps[a].iter().zip(&steps[b]).all(|steps| match steps {
+                    (Some((a, a_output)), Some((b, b_output))) => {
+                        classes[*a] == classes[*b] && a_output == b_output
+                    }
+                    (None, None) => true,
+                    _ => false,
+                })
+        };
+        let mut representatives: Vec<usize> = Vec::new();
+        let refined = (0..states.len())
+            .map(|state| {
+                match representatives
+                    .iter()
+                    .position(|representative| equivalent(*representative, state))
+                {
+                    Some(class) => class,
+                    None => {
+                        representatives.push(state);
+                        representatives.len() - 1
+                    }
+                }
+            })
+            .collect();
+        let refined_count = representatives.len();
+        classes = refined;
+        if refined_count == count {
+            break;
+        }
+        count = refined_count;
+    }
+
+    (0..count)
+        .map(|class| {
+            (0..states.len())
+                .filter(|state| classes[*state] == class)
+                .map(|state| states[state].clone())
+                .collect::<Vec<_>>()
+        })
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+/// The result of [`check_equivalence`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Equivalence<I> {
+    /// The state machines behave the same for all sequences of the inputs.
+    Equivalent,
+    /// The shortest sequence of the inputs after which the state machines
+    /// behave differently: the last input is only accepted by one of them, or
+    /// they output different things for it.
+    Diverges(Vec<I>),
+}
+
+/// Checks whether two state machines behave the same, i.e. accept the same
+/// sequences of the given inputs and output the same things for them, e.g. to
+/// compare a DSL state machine with its hand-optimized implementation.
+///
+/// The pairs of the states reachable from the initial states are explored in
+/// the breadth-first order, so the shortest diverging sequence is found. Only
+/// terminates if the state machines have finite sets of reachable states.
+#[cfg(feature = "std")]
+pub fn check_equivalence<A, B>(inputs: &[A::Input]) -> Equivalence<A::Input>
+where
+    A: StateMachineImpl,
+    B: StateMachineImpl<Input = A::Input>,
+    A::Input: Clone,
+    A::State: PartialEq,
+    B::State: PartialEq,
+    A::Output: PartialEq<B::Output>,
+{
+    let same_outputs = |a: &Option<A::Output>, b: &Option<B::Output>| match (a, b) {
+        (Some(a), Some(b)) => a == b,
+        (None, None) => true,
+        _ => false,
+    };
+    // The explored pairs of the states, along with the pair and the input they
+    // were reached from
+    let mut pairs = vec![(A::INITIAL_STATE, B::INITIAL_STATE, None::<(usize, usize)>)];
+    let mut next = 0;
+    while next < pairs.len() {
+        for (index, input) in inputs.iter().enumerate() {
+            let (a, b, _) = &pairs[next];
+            match (A::step(a, input), B::step(b, input)) {
+                (None, None) => {}
+                (Some((a, a_output)), Some((b, b_output)))
+                    if same_outputs(&a_output, &b_output) =>
+                {
+                    if !pairs
+                        .iter()
+                        .any(|(known_a, known_b, _)| *known_a == a && *known_b == b)
+                    {
+                        pairs.push((a, b, Some((next, index))));
+                    }
+                }
+                _ => {
+                    let mut sequence = vec![input.clone()];
+                    let mut from = pairs[next].2;
+                    while let Some((pair, input)) = from {
+                        sequence.push(inputs[input].clone());
+                        from = pairs[pair].2;
+                    }
+                    sequence.reverse();
+                    return Equivalence::Diverges(sequence);
+                }
+            }
+        }
+        next += 1;
+    }
+    Equivalence::Equivalent
+}
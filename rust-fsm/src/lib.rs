@@ -40,14 +40,28 @@ of state machines:
 ### Non-default
 
 - `diagram` - generate Mermaid state diagrams in the doc strings. See below.
+- `serde` - implement `Serialize` and `Deserialize` for `StateMachine` and
+  `TransitionRecord`, and allow the DSL to derive them for the generated enums.
+  See below.
 
 ## Usage in `no_std` environments
 
 This library has the feature named `std` which is enabled by default. You
 may want to import this library as
 `rust-fsm = { version = "0.8", default-features = false, features = ["dsl"] }`
-to use it in a `no_std` environment. This only affects error types (the `Error`
-trait is only available in `std`).
+to use it in a `no_std` environment. The `Error` trait is then not implemented
+for the error types, and the following APIs, which need allocations, threads
+or the system time, are not available:
+
+* `SharedStateMachine` and `QueuedStateMachine`;
+* `VecRecorder` (`RingBufferRecorder` is available) and the `EventLog`
+  implementation for `Vec`;
+* the snapshots and their migrations (`Snapshot`, `Migrations`);
+* `SystemClock` (the timeouts can be driven by another `Clock`);
+* the runtime of the nondeterministic automata (`NfaStateMachine`, `DfaTable`,
+  `Dfa`);
+* the minimization and equivalence helpers (`equivalent_states`,
+  `check_equivalence`).
 
 The DSL implementation re-export is gated by the feature named `dsl` which is
 also enabled by default.
@@ -132,6 +146,756 @@ attributes (e.g. `derive`, `repr`) are applied to it.
 Within the `state_machine` macro you must define at least one state
 transition.
 
+The transitions from a state are tried in the order of their declaration, so
+a transition without a guard always matches before the later transitions from
+the same state with the same input. The macro warns about such transitions,
+which would never be taken (the `shadowed_transitions` lint). Like the
+reachability lints below, the lint can be disabled or denied, and the denied
+shadowed transitions are errors pointing at them and at the transition
+shadowing them:
+
+```rust,compile_fail
+use rust_fsm::*;
+
+state_machine! {
+    #[state_machine(final(Running, Abandoned))]
+    #[state_machine(deny(shadowed_transitions))]
+    job(Failed)
+
+    Failed => {
+        Retry => Running,
+        Retry if true => Abandoned,
+    },
+}
+```
+
+The same goes for a transition declared more than once:
+
+```rust,compile_fail
+use rust_fsm::*;
+
+state_machine! {
+    #[state_machine(final(Running, Abandoned))]
+    #[state_machine(deny(shadowed_transitions))]
+    job(Failed)
+
+    Failed(Retry) => Running,
+    Failed(Retry) => Abandoned,
+}
+```
+
+`StateMachine::consume` returns `TransitionImpossibleError` when there is no
+transition for the current state and the provided input. To find out which
+state and input were rejected, use `StateMachine::try_consume` instead: its
+error, `TransitionRejectedError`, carries references to both of them (use
+`cloned` to get an error that no longer borrows the state machine).
+The `reason` of the error tells apart a missing transition
+(`RejectionReason::NoTransition`) from transitions whose guards all rejected
+the input (`RejectionReason::GuardsRejected`, listing the stringified guard
+expressions).
+
+#### States carrying data
+
+States can carry data, either as tuple fields (e.g. `Failed(u32)`) or as struct
+fields (e.g. `Open { since: u64 }`). The data of a state is declared once, when
+the state is used as the initial state of a transition in the compact form. A
+state that has no transitions of its own can be declared with an empty block.
+
+A transition into a state carrying data builds it with either an expression or
+a closure in parentheses after the name of the state. A closure receives
+references to the data of the current state followed by the input tuple fields,
+and returns the value (or a tuple of values in the declaration order) of the
+new state. Guards and closure outputs receive the same arguments. A transition
+from a state carrying data back to the same state without building it keeps the
+data as is (this requires the state to implement `Clone`).
+
+```rust
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(final(Finished))]
+    retry(Idle)
+
+    Idle(Start) => Running,
+    Running => {
+        Fail => Failed(1),
+        Done(u32) => Finished(|result: &u32| *result)
+    },
+    Failed(u32) => {
+        Fail if |attempts: &u32| *attempts < 3 => Failed(|attempts: &u32| attempts + 1),
+        Fail => GaveUp,
+        Retry => Running
+    },
+    Finished(u32) => {},
+    GaveUp(Reset) => Idle
+}
+
+let mut machine = retry::StateMachine::new();
+machine.consume(&retry::Input::Start).unwrap();
+machine.consume(&retry::Input::Fail).unwrap();
+machine.consume(&retry::Input::Fail).unwrap();
+assert_eq!(machine.state(), &retry::State::Failed(2));
+```
+
+The initial state of the machine cannot carry any data.
+
+#### Context
+
+A state machine may need some data that lives outside of it and is shared
+between all states, like a counter of failures (also known as the extended
+state). The type of such a context is set with
+`#[state_machine(context(...))]`. In this case the closures receive a reference
+to the context as the first argument: guards and closures building the state
+data receive `&Context`, while closure outputs receive `&mut Context` and may
+modify it. The context is passed to `StateMachine::consume_with`.
+
+```rust
+use rust_fsm::*;
+
+pub struct Failures {
+    count: u32,
+}
+
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    #[state_machine(output(crate::Failed), context(crate::Failures))]
+    circuit_breaker(Closed)
+
+    use super::{Failed, Failures};
+
+    Closed => {
+        Unsuccessful if |failures: &Failures| failures.count >= 2 => Open,
+        Unsuccessful => Closed [|failures: &mut Failures| {
+            failures.count += 1;
+            Failed(failures.count)
+        }]
+    },
+    Open(TimerTriggered) => Closed
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Failed(u32);
+
+fn main() {
+    let mut failures = Failures { count: 0 };
+    let mut machine = circuit_breaker::StateMachine::new();
+    let input = circuit_breaker::Input::Unsuccessful;
+    assert_eq!(machine.consume_with(&mut failures, &input), Ok(Some(Failed(1))));
+    machine.consume_with(&mut failures, &input).unwrap();
+    machine.consume_with(&mut failures, &input).unwrap();
+    assert_eq!(machine.state(), &circuit_breaker::State::Open);
+}
+```
+
+Without the DSL, a state machine with a context is defined by implementing the
+`StateMachineContextImpl` trait.
+
+#### Hooks
+
+A state can have hooks called when the machine enters (`on_enter`) or leaves
+(`on_exit`) it, or performs any transition from it (`on_transition`). Hooks
+are closures receiving the context (if any) and the data of the state, and the
+transition hooks also receive references to the input and the new state. They
+are called on every transition, including the ones looping back to the same
+state, after the output is computed: first the exit hooks of the current
+state, then its transition hooks, then the entry hooks of the new state.
+
+```rust
+# use rust_fsm::*;
+# pub struct Timers;
+#
+# impl Timers {
+#     fn start(&mut self) {}
+#     fn stop(&mut self) {}
+# }
+#
+# fn log<I, S>(_: &I, _: &S) {}
+#
+state_machine! {
+    #[state_machine(context(crate::Timers))]
+    circuit_breaker(Closed)
+
+    use super::{log, Timers};
+
+    Closed(Unsuccessful) => Open,
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open
+    },
+    Open => on_enter [|timers: &mut Timers| timers.start()],
+    Open => on_exit [|timers: &mut Timers| timers.stop()],
+    HalfOpen => on_transition [|_: &mut Timers, input: &Input, to: &State| {
+        log(input, to);
+    }]
+}
+# fn main() {}
+```
+
+Without the DSL, hooks are defined by implementing the `on_enter`, `on_exit`
+and `on_transition` functions of the `StateMachineImpl` trait.
+
+#### Asynchronous state machines
+
+Guards, outputs and data constructors can be `async` closures, e.g. to query a
+cache or a database. A single `async` closure makes the whole state machine
+asynchronous: the DSL implements the `AsyncStateMachineImpl` trait, and
+`StateMachine` becomes an alias of `AsyncStateMachine`, whose `consume` and
+`consume_with` functions are `async`. The futures do not depend on any
+particular executor. Hooks stay synchronous. The `async` closures are kept as
+written, so they require Rust 1.85, while the rest of the crate supports Rust
+1.79.
+
+```rust,no_run
+# use rust_fsm::*;
+# pub struct Tokens;
+#
+# impl Tokens {
+#     async fn is_valid(&self, _: u32) -> bool {
+#         true
+#     }
+# }
+#
+state_machine! {
+    #[state_machine(context(crate::Tokens))]
+    door(Locked)
+
+    use super::Tokens;
+
+    Locked => {
+        Badge(u32) if async |tokens: &Tokens, token: &u32| tokens.is_valid(*token).await => Unlocked,
+        Badge(u32) => Locked
+    },
+    Unlocked(Close) => Locked
+}
+
+# fn main() {}
+# async fn open(mut tokens: Tokens) -> Result<(), TransitionImpossibleError> {
+let mut machine = door::StateMachine::new();
+machine.consume_with(&mut tokens, &door::Input::Badge(7)).await?;
+# Ok(())
+# }
+```
+
+#### Sharing between threads
+
+`SharedStateMachine` (requires the `std` feature) is a handle to a
+`StateMachine` that can be cloned and sent to other threads. Its `consume`
+function takes `&self`, `state` returns a snapshot of the current state, and
+`consume_if` consumes the input only if the current state satisfies a
+condition, atomically:
+
+```rust
+# use rust_fsm::*;
+# state_machine! {
+#     #[derive(Clone, Copy, Debug, PartialEq)]
+#     circuit_breaker(Closed)
+#
+#     Closed(Unsuccessful) => Open [SetupTimer],
+#     Open(TimerTriggered) => HalfOpen,
+#     HalfOpen => {
+#         Successful => Closed,
+#         Unsuccessful => Open [SetupTimer]
+#     }
+# }
+# #[cfg(feature = "std")]
+# fn main() {
+let machine = SharedStateMachine::<circuit_breaker::Impl>::new();
+let res = machine.consume_if(
+    |state| *state == circuit_breaker::State::HalfOpen,
+    &circuit_breaker::Input::Unsuccessful,
+);
+# assert_eq!(res, Err(ConsumeIfError::ConditionNotMet));
+# }
+# #[cfg(not(feature = "std"))]
+# fn main() {}
+```
+
+#### Lock-free state machines
+
+When all states are fieldless and the `#[repr(u8)]` attribute is set, the DSL
+implements the `AtomicState` trait for the generated `State` enum.
+`AtomicStateMachine` stores such a state in an `AtomicU8` and performs
+transitions with a compare-exchange loop, without any locks. `retries` reports
+how many times a transition was retried because of a concurrent change of the
+state. Every attempt calls the transition function once, so the guards may run
+again on a retry, while the output function and the hooks only run once the
+state is replaced. The outputs and the hooks of concurrent transitions may run
+in any order.
+
+```rust
+# use rust_fsm::*;
+state_machine! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(u8)]
+    circuit_breaker(Closed)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [SetupTimer]
+    }
+}
+
+# fn main() -> Result<(), TransitionImpossibleError> {
+let machine = AtomicStateMachine::<circuit_breaker::Impl>::new();
+machine.consume(&circuit_breaker::Input::Unsuccessful)?;
+# assert_eq!(machine.state(), circuit_breaker::State::Open);
+# Ok(())
+# }
+```
+
+#### Event queue
+
+`QueuedStateMachine` (requires the `std` feature) queues external events with
+`push` and consumes them in the run-to-completion order with `run`. The
+handler of the outputs may raise internal events, which are consumed before
+the external ones. A single run consumes at most `step_budget` events
+(configurable with `with_step_budget`) to stop infinite loops of internal
+events.
+
+```rust
+# use rust_fsm::*;
+# state_machine! {
+#     #[derive(Clone, Debug, PartialEq)]
+#     order(Placed)
+#
+#     Placed(Pay) => Reserving [ReserveStock],
+#     Reserving(StockReserved) => Shipping [Ship],
+#     Shipping(Shipped) => Delivered,
+# }
+# #[cfg(feature = "std")]
+# fn main() -> Result<(), Box<dyn std::error::Error>> {
+let mut queue = QueuedStateMachine::<order::Impl>::new();
+queue.push(order::Input::Pay);
+queue.run(|output, raise| match output {
+    order::Output::ReserveStock => raise.raise(order::Input::StockReserved),
+    order::Output::Ship => raise.raise(order::Input::Shipped),
+})?;
+# assert_eq!(queue.state(), &order::State::Delivered);
+# Ok(())
+# }
+# #[cfg(not(feature = "std"))]
+# fn main() {}
+```
+
+#### Timeouts
+
+A state can fire an input once the machine stays in it for some time:
+`Open => after(5s) => HalfOpen` (also available in the compact form as
+`after(5s) => HalfOpen`). The duration is an integer with one of the `ms`, `s`,
+`min` or `h` suffixes. The fired input is `Timeout` unless specified as
+`after(5s, TimerTriggered)`, and it can also be consumed as any other input.
+
+The DSL implements the `TimeoutImpl` trait for such state machines.
+`TimedStateMachine` schedules the timeouts against a `Clock` every time the
+machine enters a state, and `poll_timeout` fires the expired ones. `MockClock`
+is only moved manually, so the timeouts can be tested without waiting:
+
+```rust
+# use rust_fsm::*;
+# use std::time::Duration;
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    circuit_breaker(Closed)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open => after(5s) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [SetupTimer]
+    }
+}
+
+# fn main() -> Result<(), TransitionImpossibleError> {
+let clock = MockClock::new();
+let mut machine = TimedStateMachine::<circuit_breaker::Impl, _>::new(&clock);
+machine.consume(&circuit_breaker::Input::Unsuccessful)?;
+clock.advance(Duration::from_secs(5));
+machine.poll_timeout();
+assert_eq!(machine.state(), &circuit_breaker::State::HalfOpen);
+# Ok(())
+# }
+```
+
+#### Transition history
+
+`StateMachine` passes every performed transition to a `TransitionRecorder`,
+which records nothing by default. `with_recorder` replaces it with
+`RingBufferRecorder`, keeping the last `N` transitions without allocating, or
+with `VecRecorder` (requires the `std` feature), keeping all of them. The
+records (the state before the transition, the input, the new state and the
+output) can be iterated, filtered and cleared:
+
+```rust
+# use rust_fsm::*;
+# state_machine! {
+#     #[derive(Clone, Debug, PartialEq)]
+#     order(Placed)
+#
+#     Placed => {
+#         Pay => Paid,
+#         Decline => Failed [Refunded],
+#     },
+#     Failed(Retry) => Placed,
+# }
+# #[cfg(feature = "std")]
+# fn main() {
+let mut machine = order::StateMachine::new().with_recorder(VecRecorder::new());
+// ...
+let failed_from = machine
+    .recorder()
+    .iter()
+    .filter(|record| record.to == order::State::Failed)
+    .map(|record| &record.from);
+# assert_eq!(failed_from.count(), 0);
+machine.recorder_mut().clear();
+# }
+# #[cfg(not(feature = "std"))]
+# fn main() {}
+```
+
+#### Event sourcing
+
+`StateMachine::replay` rebuilds a state machine from the inputs it consumed,
+starting from the initial state. The outputs are computed and discarded, so
+the changes they make to the context (passed to `StateMachine::replay_with`)
+are replayed, while the hooks are not called. `EventSourcedStateMachine`
+appends every accepted input to an `EventLog` (such as a `Vec`), and is
+restored from the log after a restart. If the definition has changed and an
+input of the log is rejected, a `ReplayError` reports its position, the input
+and the state reached before it:
+
+```rust
+# use rust_fsm::*;
+# state_machine! {
+#     #[derive(Clone, Debug, PartialEq)]
+#     order(Placed)
+#
+#     Placed(Pay(u32)) => Paid,
+#     Paid(Ship) => Shipped,
+# }
+# #[cfg(feature = "std")]
+# fn main() -> Result<(), Box<dyn std::error::Error>> {
+let mut machine = EventSourcedStateMachine::<order::Impl, _>::new(Vec::new());
+machine.consume(&order::Input::Pay(10))?;
+# let (_, log) = machine.into_parts();
+
+// After a restart
+let machine = EventSourcedStateMachine::<order::Impl, _>::restore(log)?;
+# assert_eq!(machine.state(), &order::State::Paid);
+# Ok(())
+# }
+# #[cfg(not(feature = "std"))]
+# fn main() {}
+```
+
+#### Serde
+
+With the `serde` feature, `StateMachine` implements `Serialize` and
+`Deserialize` whenever its state and its history do, so it can be persisted and
+restored. The state and the substates remembered for the history pseudo-states
+are serialized, the recorder is reset on deserialization. The DSL derives
+`Serialize` and `Deserialize` for the generated enums listed in
+`#[state_machine(serde(...))]` (any of `state`, `input` and `output`, `state`
+also covering the generated `History`), so there is no need to add the derives
+to all generated types:
+
+```rust
+# use rust_fsm::*;
+# #[cfg(feature = "serde")]
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(serde(state, input))]
+    workflow(Draft)
+
+    Draft(Submit) => Review,
+    Review => {
+        Approve => Approved,
+        Reject => Draft
+    }
+}
+
+# #[cfg(feature = "serde")]
+# fn main() -> Result<(), serde_json::Error> {
+# let machine = workflow::StateMachine::new();
+let json = serde_json::to_string(&machine)?;
+let machine: workflow::StateMachine = serde_json::from_str(&json)?;
+# assert_eq!(machine.state(), &workflow::State::Draft);
+# Ok(())
+# }
+# #[cfg(not(feature = "serde"))]
+# fn main() {}
+```
+
+#### Snapshots and migrations
+
+The DSL names the states of the generated `State` enum (see the
+`SnapshotImpl` trait), so `StateMachine::snapshot` can record the name of the
+current state along with the version of the definition, set with
+`#[state_machine(version(...))]` (`0` by default). A `Snapshot` (serializable
+with the `serde` feature) is restored by `StateMachine::from_snapshot` with a
+registry of `Migrations`, mapping the renamed or removed states of the older
+versions to the new states. The other states are restored by name, and the
+restoration fails on states that no longer exist. As only the name of the
+state is recorded, taking a snapshot of a state carrying data fails with
+`SnapshotError` instead of losing the data:
+
+```rust
+# use rust_fsm::*;
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(version(2))]
+    order(AwaitingPayment)
+
+    AwaitingPayment(Pay) => Paid,
+    // ...
+}
+
+# #[cfg(feature = "std")]
+# fn main() -> Result<(), MigrationError> {
+# let snapshot = Snapshot {
+#     version: 1,
+#     state: "Done".to_string(),
+# };
+let migrations = Migrations::<order::Impl>::new()
+    .rename("Pending", order::State::AwaitingPayment)
+    .rename_in(1, "Done", order::State::Paid);
+let machine = order::StateMachine::from_snapshot(&snapshot, &migrations)?;
+# assert_eq!(machine.state(), &order::State::Paid);
+# Ok(())
+# }
+# #[cfg(not(feature = "std"))]
+# fn main() {}
+```
+
+The generated module also contains the `DEFINITION_HASH` constant, the
+fingerprint of the initial state, the states, the inputs, the outputs and the
+transitions of the definition. It changes whenever the state machine graph
+changes, but not when the definition is only reformatted, so it can be stored
+along with the snapshots to detect that they were taken with a different
+definition.
+
+#### Hierarchical states
+
+A composite state is declared with its initial substate and a block of
+substates, which may be composite states themselves. Entering a composite
+state enters its initial substate, and the transitions and hooks declared for
+a composite state (next to its block) apply to all of its substates. The
+innermost transitions take precedence, so a substate can override an inherited
+transition. A substate without its own transitions is declared by its name:
+
+```rust
+# use rust_fsm::*;
+# fn log(_: &str) {}
+#
+state_machine! {
+    connection(Disconnected)
+
+    use super::log;
+
+    Disconnected(Connect) => Connected,
+    Connected(Handshake) {
+        Handshake(Accepted) => Ready,
+        Ready(Send) => Busy,
+        Busy(Done) => Ready,
+        Paused,
+    },
+    Connected => {
+        Disconnect => Disconnected,
+        Pause => Paused,
+    },
+    Connected => on_exit [|| log("disconnected")]
+}
+# fn main() {}
+```
+
+The generated `State` enum only contains the top-level states, and every
+composite state holds the enum of its substates (e.g.,
+`State::Connected(ConnectedState::Ready)`). The entry and exit hooks of a
+composite state are only called when the machine crosses its boundary, not on
+the transitions between its substates, while its transition hooks are called
+on every transition from any of its substates. Composite states cannot carry data or have timeouts,
+and they are not supported with custom state types.
+
+#### History
+
+A transition can enter a composite state through its history pseudo-state
+instead of its initial substate: `=> Active(H)` resumes the substate the
+machine was last in within `Active` (entering a composite substate in its
+initial substate), and `=> Active(H*)` resumes the innermost substates,
+along with their data. The initial substate is entered if the machine has not
+been in the composite state yet:
+
+```rust
+# use rust_fsm::*;
+state_machine! {
+    #[derive(Clone)]
+    player(Stopped)
+
+    Stopped(Play) => Active,
+    Active(Playing) {
+        Playing(Pause) => Paused,
+        Paused(Play) => Playing,
+    },
+    Active(Interrupt) => Interrupted,
+    Interrupted(Resume) => Active(H)
+}
+# fn main() {}
+```
+
+The remembered substates are not a part of the state: they are stored by the
+`StateMachine` wrapper in its history (the `SubstateHistory` of the
+`HistoryImpl` implemented by DSL, accessible with `StateMachine::history`),
+so the substate enums must implement `Clone`. The transition functions of the
+`Impl` type still enter the initial substates. History is not supported by
+asynchronous state machines and in regions.
+
+#### Orthogonal regions
+
+Independent concerns of a state machine can be declared as parallel regions
+instead of a single state enum with every combination of their states. The
+regions are named along with their initial states and are separated by `--`:
+
+```rust
+# use rust_fsm::*;
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    device(power: Awake, link: Offline)
+
+    Awake(Sleep) => Asleep [Dimmed],
+    Asleep(Wake) => Awake,
+    --
+    Offline(Connect) => Online [Connected],
+    Online => {
+        Disconnect => Offline,
+        Sleep => Offline [Disconnected]
+    }
+}
+# fn main() {}
+```
+
+Every region is a state machine generated in its own module (e.g.,
+`device::power`) and sharing the `Input` and `Output` enums of the enclosing
+module. The state of the enclosing state machine is the tuple of the states of
+the regions (e.g., `(power::State, link::State)`), so the states must
+implement `Clone`. Every input is passed to all regions: the regions without a
+transition for it keep their states, and it is only rejected when all regions
+reject it. The output is the array of the outputs of the regions (e.g.,
+`[Some(Output::Dimmed), Some(Output::Disconnected)]`), or `None` when no
+region outputs anything. The regions share the context, but they cannot have
+hooks or timeouts, and they are not supported by asynchronous state machines
+or with custom alphabet types.
+
+#### Minimization
+
+State machines growing over time may end up with equivalent states: the states
+that can be merged without changing the behavior of the state machine, as
+every input either is rejected in all of them, or produces the same output and
+leads to equivalent states. `#[state_machine(minimize)]` finds them by the
+partition refinement and reports them with a warning:
+
+```rust
+# use rust_fsm::*;
+state_machine! {
+    #[state_machine(minimize)]
+    door(Closed)
+
+    Closed(Push) => Open,
+    Open => {
+        Close => Closed [Slammed],
+        Push => Wide,
+    },
+    Wide => {
+        Close => Closed [Slammed],
+        Push => Wide,
+    },
+}
+# fn main() {}
+```
+
+```text
+warning: use of deprecated constant `door::minimize`: the states `Open` and `Wide` are equivalent and can be merged
+```
+
+The groups of the equivalent states are also listed in the generated
+`EQUIVALENT_STATES` constant (e.g., `&[&["Open", "Wide"]]`). The guards, the
+closures and the hooks are compared by their code, so the states are only
+reported as equivalent if they use the same ones.
+
+The analysis is also available at runtime: DSL implements the `IntrospectImpl`
+trait, listing all states and inputs, for the state machines without a context
+whose states and inputs carry no data, and `equivalent_states::<door::Impl>()`
+(requires `std`) returns the groups of the equivalent states. `IntrospectImpl`
+can also be implemented by hand.
+
+#### Reachability lints
+
+The `state_machine` macro checks that every state can be reached from the
+initial state (the `unreachable_states` lint), and that every state has
+outgoing transitions, including the ones of the composite states containing
+it, unless it is declared as final (the `dead_end_states` lint). The state
+machines declaring no final states may end in any state, so they are only
+checked for dead ends once the lint is set explicitly (e.g., with
+`#[state_machine(warn(dead_end_states))]`):
+
+```rust
+# use rust_fsm::*;
+state_machine! {
+    #[state_machine(final(Shipped))]
+    order(Placed)
+
+    Placed => {
+        Pay => Paid,
+        Cancel => Cancelled,
+    },
+    Paid(Ship) => Shipped,
+}
+# fn main() {}
+```
+
+The problems are reported as warnings, so the existing definitions with
+unreachable states keep compiling:
+
+```text
+warning: use of deprecated constant `order::dead_end_states::Cancelled`: the state `Cancelled` has no outgoing transitions, but it is not declared as final (`#[state_machine(final(Cancelled))]`)
+```
+
+The lints can be turned into errors with `#[state_machine(deny(..))]`, or
+disabled with `#[state_machine(allow(..))]`:
+
+```rust,compile_fail
+use rust_fsm::*;
+
+state_machine! {
+    #[state_machine(deny(unreachable_states, dead_end_states))]
+    order(Placed)
+
+    Placed(Pay) => Paid,
+    Refunded(Pay) => Paid,
+}
+```
+
+The final states must be declared by the state machine, or by any of its
+regions, so a misspelled final state is an error:
+
+```rust,compile_fail
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone)]
+    #[state_machine(final(Of))]
+    device(power: Awake, link: Offline)
+
+    Awake(Shutdown) => Off,
+    --
+    Offline(Connect) => Online,
+    Online(Disconnect) => Offline,
+}
+```
+
 #### Visibility
 
 You can specify visibility like this:
@@ -208,17 +972,171 @@ cargo doc -p doc-example --open
 
 ### Without DSL
 
-The `state_machine` macro has limited capabilities (for example, a state
-cannot carry any additional data), so in certain complex cases a user might
-want to write a more complex state machine by hand.
+The `state_machine` macro has limited capabilities, so in certain complex
+cases a user might want to write a more complex state machine by hand.
 
 All you need to do to build a state machine is to implement the
 `StateMachineImpl` trait and use it in conjuctions with some of the provided
-wrappers (for now there is only `StateMachine`).
+wrappers (for now there is only `StateMachine`). Asynchronous state machines
+implement the `AsyncStateMachineImpl` trait and are used with
+`AsyncStateMachine`.
 
 You can see an example of the Circuit Breaker state machine in the
 [project repository][repo].
 
+#### Nondeterministic automata
+
+A nondeterministic automaton implements the `NfaImpl` trait, where an input
+leads from a state to any number of states, and `epsilon` lists the states
+reachable without consuming any input. `NfaStateMachine` (requires `std`)
+runs it by tracking the set of the active states:
+
+```rust
+# use rust_fsm::*;
+# #[cfg(feature = "std")]
+# fn main() {
+struct EndsWithAb;
+
+impl NfaImpl for EndsWithAb {
+    type Input = char;
+    type State = u8;
+    const INITIAL_STATE: u8 = 0;
+
+    fn transition(state: &u8, input: &char) -> impl IntoIterator<Item = u8> {
+        match (state, input) {
+            (0, 'a') => vec![0, 1],
+            (0, _) => vec![0],
+            (1, 'b') => vec![2],
+            _ => vec![],
+        }
+    }
+
+    fn is_accepting(state: &u8) -> bool {
+        *state == 2
+    }
+}
+
+let mut machine = NfaStateMachine::<EndsWithAb>::new();
+machine.consume(&'a').unwrap();
+machine.consume(&'b').unwrap();
+assert!(machine.is_accepting());
+# }
+# #[cfg(not(feature = "std"))]
+# fn main() {}
+```
+
+`DfaTable::build` converts the automaton over the given input alphabet to an
+equivalent deterministic transition table by the subset construction. A type
+implementing `DfaImpl` provides the table (usually built once in a static
+`OnceLock`), and `Dfa` drives `StateMachine` with it: the states are the
+indices of the table states and the output is `Accepted` whenever an
+accepting state is entered.
+
+#### Product of state machines
+
+`Product<A, B>` runs two state machines sharing the input type in lockstep,
+e.g. a protocol checker next to a business workflow. It implements
+`StateMachineImpl`, so it is used with `StateMachine` like any other state
+machine:
+
+```rust
+# use rust_fsm::*;
+# #[derive(Clone, Copy, Debug, PartialEq)]
+# pub enum Message {
+#     Hello,
+#     Order,
+# }
+#
+# state_machine! {
+#     #[derive(Clone, Debug, PartialEq)]
+#     #[state_machine(input(crate::Message))]
+#     protocol(Idle)
+#
+#     Idle(Hello) => Session,
+#     Session(Order) => Session,
+# }
+#
+# state_machine! {
+#     #[derive(Clone, Debug, PartialEq)]
+#     #[state_machine(input(crate::Message))]
+#     workflow(Empty)
+#
+#     Empty(Order) => Ordered [Reserved],
+#     Ordered(Order) => Ordered [Reserved],
+# }
+#
+# fn main() {
+let mut machine = StateMachine::<Product<protocol::Impl, workflow::Impl>>::new();
+# assert!(machine.consume(&Message::Hello).is_err());
+# }
+```
+
+Its state is the pair of the states of the state machines, and its output is
+the pair of their outputs (e.g., `(None, Some(workflow::Output::Reserved))`),
+or `None` when neither outputs anything. By default an input is only accepted
+if both state machines accept it. With the `Either` mode
+(`Product<A, B, Either>`), it is accepted if any of them does, and the state
+machine rejecting it keeps its state. The hooks of the state machines are
+called on the transitions they perform. In the `Either` mode, the transitions
+are evaluated again to find out which state machines accepted the input, so
+their guards run twice.
+
+#### Equivalence checking
+
+`check_equivalence` (requires `std`) checks whether two state machines sharing
+the input type behave the same, e.g. a DSL state machine and its
+hand-optimized implementation. It explores the pairs of their states reachable
+with the given inputs in the breadth-first order, and returns either
+`Equivalence::Equivalent` or the shortest input sequence after which they
+diverge, i.e. the last input is accepted by only one of them or produces
+different outputs:
+
+```rust
+# use rust_fsm::*;
+# state_machine! {
+#     #[derive(Clone, Debug, PartialEq)]
+#     door(Closed)
+#
+#     Closed(Push) => Open,
+#     Open(Close) => Closed [Slammed],
+# }
+#
+# struct MinimalDoor;
+#
+# impl StateMachineImpl for MinimalDoor {
+#     type Input = door::Input;
+#     type State = bool;
+#     type Output = door::Output;
+#     const INITIAL_STATE: bool = false;
+#
+#     fn transition(open: &bool, input: &door::Input) -> Option<bool> {
+#         match (open, input) {
+#             (false, door::Input::Push) => Some(true),
+#             (true, door::Input::Close) => Some(false),
+#             _ => None,
+#         }
+#     }
+#
+#     fn output(open: &bool, input: &door::Input) -> Option<door::Output> {
+#         (*open && *input == door::Input::Close).then_some(door::Output::Slammed)
+#     }
+# }
+#
+# #[cfg(feature = "std")]
+# fn main() {
+let inputs = <door::Impl as IntrospectImpl>::INPUTS;
+match check_equivalence::<door::Impl, MinimalDoor>(inputs) {
+    Equivalence::Equivalent => {}
+    Equivalence::Diverges(inputs) => panic!("diverged after {inputs:?}"),
+}
+# }
+# #[cfg(not(feature = "std"))]
+# fn main() {}
+```
+
+The states of the state machines must implement `PartialEq`, and their sets
+of reachable states must be finite.
+
 [repo]: https://github.com/eugene-babichenko/rust-fsm
 [docs-badge]: https://docs.rs/rust-fsm/badge.svg
 [docs-link]: https://docs.rs/rust-fsm
@@ -233,12 +1151,60 @@ use core::fmt;
 #[cfg(feature = "std")]
 use std::error::Error;
 
+mod asynchronous;
+#[cfg(all(target_has_atomic = "8", target_has_atomic = "ptr"))]
+mod atomic;
+mod event_sourcing;
+mod history;
+mod introspect;
+mod nfa;
+mod product;
+#[cfg(feature = "std")]
+mod queue;
+mod recorder;
+#[cfg(feature = "std")]
+mod shared;
+mod snapshot;
+mod timeout;
+
+pub use asynchronous::{AsyncStateMachine, AsyncStateMachineImpl};
+#[cfg(all(target_has_atomic = "8", target_has_atomic = "ptr"))]
+pub use atomic::{AtomicState, AtomicStateMachine};
+pub use event_sourcing::{EventLog, EventSourcedStateMachine, ReplayError};
+pub use history::{History, HistoryImpl, NoHistory, SubstateHistory};
+pub use introspect::IntrospectImpl;
+#[cfg(feature = "std")]
+pub use introspect::{check_equivalence, equivalent_states, Equivalence};
+pub use nfa::NfaImpl;
+#[cfg(feature = "std")]
+pub use nfa::{Accepted, Dfa, DfaImpl, DfaTable, NfaStateMachine};
+pub use product::{Both, Either, Product, ProductMode};
+#[cfg(feature = "std")]
+pub use queue::{QueuedStateMachine, Raise, RunError};
+#[cfg(feature = "std")]
+pub use recorder::VecRecorder;
+pub use recorder::{
+    NoRecorder, RecordOf, RingBufferRecorder, TransitionRecord, TransitionRecorder,
+};
+#[cfg(feature = "std")]
+pub use shared::{ConsumeIfError, SharedStateMachine};
+pub use snapshot::SnapshotImpl;
+#[cfg(feature = "std")]
+pub use snapshot::{MigrationError, Migrations, Snapshot, SnapshotError};
+#[cfg(feature = "std")]
+pub use timeout::SystemClock;
+pub use timeout::{Clock, MockClock, TimedStateMachine, TimeoutImpl};
+
 #[cfg(feature = "dsl")]
 pub use rust_fsm_dsl::state_machine;
 
 #[cfg(feature = "diagram")]
 pub use aquamarine::aquamarine;
 
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde;
+
 /// This trait is designed to describe any possible deterministic finite state
 /// machine/transducer. This is just a formal definition that may be
 /// inconvenient to be used in practical programming, but it is used throughout
@@ -262,13 +1228,179 @@ pub trait StateMachineImpl {
     /// based on the current state and the given input. Outputs `None` when
     /// there is no output for a given combination of the input and the state.
     fn output(state: &Self::State, input: &Self::Input) -> Option<Self::Output>;
+    /// The combination of the transition and the output functions: outputs the
+    /// new state along with the output, or `None` when there is no transition
+    /// for a given combination of the input and the state. This is the
+    /// function actually used by [`StateMachine`], so implementations may
+    /// override it to evaluate their conditions only once. Calls the
+    /// transition and the output functions by default.
+    fn step(
+        state: &Self::State,
+        input: &Self::Input,
+    ) -> Option<(Self::State, Option<Self::Output>)> {
+        let new_state = Self::transition(state, input)?;
+        Some((new_state, Self::output(state, input)))
+    }
+    /// Explains why the transition function outputs `None` for a given
+    /// combination of the input and the state. Only meaningful when the
+    /// transition function actually outputs `None`. Returns
+    /// [`RejectionReason::NoTransition`] by default.
+    fn rejection_reason(_state: &Self::State, _input: &Self::Input) -> RejectionReason {
+        RejectionReason::NoTransition
+    }
+    /// The hook called when the machine leaves the given state, after the
+    /// output is computed. Called on every transition, including the ones
+    /// looping back to the same state. Does nothing by default.
+    fn on_exit(_state: &Self::State) {}
+    /// The hook called on every transition after the exit hook of the
+    /// current state. Does nothing by default.
+    fn on_transition(_from: &Self::State, _input: &Self::Input, _to: &Self::State) {}
+    /// The hook called when the machine enters the given state, after the
+    /// state is updated. Called on every transition, including the ones
+    /// looping back to the same state. Does nothing by default.
+    fn on_enter(_state: &Self::State) {}
+}
+
+/// The same as [`StateMachineImpl`], but the transition and output functions
+/// also have access to a context (also known as the extended state) that lives
+/// outside of the machine, e.g. a counter of failures. The transition function
+/// may only read the context, while the output function may also modify it.
+///
+/// This trait is implemented for every [`StateMachineImpl`] with `()` as the
+/// context.
+pub trait StateMachineContextImpl {
+    /// The input alphabet.
+    type Input;
+    /// The set of possible states.
+    type State;
+    /// The output alphabet.
+    type Output;
+    /// The context passed to the transition and output functions.
+    type Context;
+    /// The initial state of the machine.
+    // allow since there is usually no interior mutability because states are enums
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INITIAL_STATE: Self::State;
+    /// The transition fuction that outputs a new state based on the current
+    /// state, the provided input and the context. Outputs `None` when there is
+    /// no transition for a given combination of the input and the state.
+    fn transition_with(
+        state: &Self::State,
+        input: &Self::Input,
+        context: &Self::Context,
+    ) -> Option<Self::State>;
+    /// The output function that outputs some value from the output alphabet
+    /// based on the current state, the given input and the context. Outputs
+    /// `None` when there is no output for a given combination of the input and
+    /// the state.
+    fn output_with(
+        state: &Self::State,
+        input: &Self::Input,
+        context: &mut Self::Context,
+    ) -> Option<Self::Output>;
+    /// The same as [`StateMachineImpl::step`], but with the context. Calls the
+    /// transition and the output functions by default.
+    fn step_with(
+        state: &Self::State,
+        input: &Self::Input,
+        context: &mut Self::Context,
+    ) -> Option<(Self::State, Option<Self::Output>)> {
+        let new_state = Self::transition_with(state, input, context)?;
+        Some((new_state, Self::output_with(state, input, context)))
+    }
+    /// The same as [`StateMachineImpl::rejection_reason`], but with the
+    /// context.
+    fn rejection_reason_with(
+        _state: &Self::State,
+        _input: &Self::Input,
+        _context: &Self::Context,
+    ) -> RejectionReason {
+        RejectionReason::NoTransition
+    }
+    /// The same as [`StateMachineImpl::on_exit`], but with the context.
+    fn on_exit_with(_state: &Self::State, _context: &mut Self::Context) {}
+    /// The same as [`StateMachineImpl::on_transition`], but with the context.
+    fn on_transition_with(
+        _from: &Self::State,
+        _input: &Self::Input,
+        _to: &Self::State,
+        _context: &mut Self::Context,
+    ) {
+    }
+    /// The same as [`StateMachineImpl::on_enter`], but with the context.
+    fn on_enter_with(_state: &Self::State, _context: &mut Self::Context) {}
+}
+
+impl<T> StateMachineContextImpl for T
+where
+    T: StateMachineImpl,
+{
+    type Input = T::Input;
+    type State = T::State;
+    type Output = T::Output;
+    type Context = ();
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INITIAL_STATE: Self::State = T::INITIAL_STATE;
+
+    fn transition_with(state: &Self::State, input: &Self::Input, _: &()) -> Option<Self::State> {
+        T::transition(state, input)
+    }
+
+    fn output_with(state: &Self::State, input: &Self::Input, _: &mut ()) -> Option<Self::Output> {
+        T::output(state, input)
+    }
+
+    fn step_with(
+        state: &Self::State,
+        input: &Self::Input,
+        _: &mut (),
+    ) -> Option<(Self::State, Option<Self::Output>)> {
+        T::step(state, input)
+    }
+
+    fn rejection_reason_with(state: &Self::State, input: &Self::Input, _: &()) -> RejectionReason {
+        T::rejection_reason(state, input)
+    }
+
+    fn on_exit_with(state: &Self::State, _: &mut ()) {
+        T::on_exit(state)
+    }
+
+    fn on_transition_with(from: &Self::State, input: &Self::Input, to: &Self::State, _: &mut ()) {
+        T::on_transition(from, input, to)
+    }
+
+    fn on_enter_with(state: &Self::State, _: &mut ()) {
+        T::on_enter(state)
+    }
 }
 
 /// A convenience wrapper around the `StateMachine` trait that encapsulates the
 /// state and transition and output function calls.
+///
+/// The performed transitions are passed to the recorder `R`, which records
+/// nothing by default (see [`StateMachine::with_recorder`]). The history `H`
+/// resumes the substates of the composite states entered through their
+/// history, and remembers nothing by default (see [`SubstateHistory`]).
 #[derive(Debug, Clone)]
-pub struct StateMachine<T: StateMachineImpl> {
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "T::State: serde::Serialize, H: History<T> + serde::Serialize",
+        deserialize = "T::State: serde::Deserialize<'de>, R: Default, \
+                       H: Default + serde::Deserialize<'de>"
+    ))
+)]
+pub struct StateMachine<T: StateMachineContextImpl, R = NoRecorder, H = NoHistory> {
     state: T::State,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    recorder: R,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "History::<T>::is_empty")
+    )]
+    history: H,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -276,35 +1408,137 @@ pub struct StateMachine<T: StateMachineImpl> {
 /// the current combination of state and input.
 pub struct TransitionImpossibleError;
 
-impl<T> StateMachine<T>
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The same as [`TransitionImpossibleError`], but carries the state and the
+/// rejected input (or references to them), and the reason of the rejection.
+pub struct TransitionRejectedError<S, I> {
+    /// The state of the machine when the input was rejected.
+    pub state: S,
+    /// The rejected input.
+    pub input: I,
+    /// Why the input was rejected.
+    pub reason: RejectionReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The reason why a state transition is impossible.
+pub enum RejectionReason {
+    /// There is no transition defined for the given combination of the state
+    /// and the input.
+    NoTransition,
+    /// There are transitions defined for the given combination of the state
+    /// and the input, but all of them are guarded and every guard returned
+    /// `false`. Contains the stringified guard expressions in the order of
+    /// their evaluation.
+    GuardsRejected(&'static [&'static str]),
+}
+
+impl<T, R, H> StateMachine<T, R, H>
 where
-    T: StateMachineImpl,
+    T: StateMachineContextImpl,
+    R: TransitionRecorder<T>,
+    H: History<T>,
 {
     /// Create a new instance of this wrapper which encapsulates the initial
     /// state.
-    pub fn new() -> Self {
+    pub fn new() -> Self
+    where
+        R: Default,
+        H: Default,
+    {
         Self::from_state(T::INITIAL_STATE)
     }
 
     /// Create a new instance of this wrapper which encapsulates the given
     /// state.
-    pub fn from_state(state: T::State) -> Self {
-        Self { state }
+    pub fn from_state(state: T::State) -> Self
+    where
+        R: Default,
+        H: Default,
+    {
+        Self {
+            state,
+            recorder: R::default(),
+            history: H::default(),
+        }
+    }
+
+    /// Replaces the recorder of the performed transitions, e.g. with
+    /// [`RingBufferRecorder`] or [`VecRecorder`].
+    pub fn with_recorder<U>(self, recorder: U) -> StateMachine<T, U, H>
+    where
+        U: TransitionRecorder<T>,
+    {
+        StateMachine {
+            state: self.state,
+            recorder,
+            history: self.history,
+        }
+    }
+
+    /// Returns the recorder of the performed transitions.
+    pub fn recorder(&self) -> &R {
+        &self.recorder
+    }
+
+    /// Returns the recorder of the performed transitions, e.g. to clear it.
+    pub fn recorder_mut(&mut self) -> &mut R {
+        &mut self.recorder
+    }
+
+    /// Returns the history of the composite states.
+    pub fn history(&self) -> &H {
+        &self.history
+    }
+
+    /// Returns the history of the composite states, e.g. to clear it.
+    pub fn history_mut(&mut self) -> &mut H {
+        &mut self.history
     }
 
     /// Consumes the provided input, gives an output and performs a state
-    /// transition. If a state transition with the current state and the
-    /// provided input is not allowed, returns an error.
-    pub fn consume(
+    /// transition using the provided context. If a state transition with the
+    /// current state, the provided input and the context is not allowed,
+    /// returns an error.
+    ///
+    /// The new state and the output are computed at once by
+    /// [`StateMachineContextImpl::step_with`] (through the history, which
+    /// remembers the current state first). Then the hooks are called in the
+    /// following order: the exit hook of the current state, the transition
+    /// hook and the entry hook of the new state. The transition is recorded
+    /// right after the transition hook.
+    pub fn consume_with(
         &mut self,
+        context: &mut T::Context,
         input: &T::Input,
     ) -> Result<Option<T::Output>, TransitionImpossibleError> {
-        if let Some(state) = T::transition(&self.state, input) {
-            let output = T::output(&self.state, input);
+        self.try_consume_with(context, input)
+            .map_err(TransitionImpossibleError::from)
+    }
+
+    /// The same as [`StateMachine::consume_with`], but the error carries
+    /// references to the current state and the rejected input.
+    #[allow(clippy::type_complexity)]
+    pub fn try_consume_with<'a>(
+        &'a mut self,
+        context: &mut T::Context,
+        input: &'a T::Input,
+    ) -> Result<Option<T::Output>, TransitionRejectedError<&'a T::State, &'a T::Input>> {
+        self.history.remember(&self.state);
+        if let Some((state, output)) = self.history.step_with(&self.state, input, context) {
+            T::on_exit_with(&self.state, context);
+            T::on_transition_with(&self.state, input, &state, context);
+            self.recorder
+                .record(&self.state, input, &state, output.as_ref());
             self.state = state;
+            T::on_enter_with(&self.state, context);
             Ok(output)
         } else {
-            Err(TransitionImpossibleError)
+            Err(TransitionRejectedError {
+                state: &self.state,
+                input,
+                reason: T::rejection_reason_with(&self.state, input, context),
+            })
         }
     }
 
@@ -314,9 +1548,38 @@ where
     }
 }
 
-impl<T> Default for StateMachine<T>
+impl<T, R, H> StateMachine<T, R, H>
 where
-    T: StateMachineImpl,
+    T: StateMachineContextImpl<Context = ()>,
+    R: TransitionRecorder<T>,
+    H: History<T>,
+{
+    /// Consumes the provided input, gives an output and performs a state
+    /// transition. If a state transition with the current state and the
+    /// provided input is not allowed, returns an error.
+    pub fn consume(
+        &mut self,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        self.consume_with(&mut (), input)
+    }
+
+    /// The same as [`StateMachine::consume`], but the error carries
+    /// references to the current state and the rejected input.
+    #[allow(clippy::type_complexity)]
+    pub fn try_consume<'a>(
+        &'a mut self,
+        input: &'a T::Input,
+    ) -> Result<Option<T::Output>, TransitionRejectedError<&'a T::State, &'a T::Input>> {
+        self.try_consume_with(&mut (), input)
+    }
+}
+
+impl<T, R, H> Default for StateMachine<T, R, H>
+where
+    T: StateMachineContextImpl,
+    R: TransitionRecorder<T> + Default,
+    H: History<T> + Default,
 {
     fn default() -> Self {
         Self::new()
@@ -338,3 +1601,66 @@ impl Error for TransitionImpossibleError {
         None
     }
 }
+
+impl<S, I> TransitionRejectedError<&S, &I>
+where
+    S: Clone,
+    I: Clone,
+{
+    /// Clones the state and the input so the error no longer borrows the
+    /// state machine.
+    pub fn cloned(self) -> TransitionRejectedError<S, I> {
+        TransitionRejectedError {
+            state: self.state.clone(),
+            input: self.input.clone(),
+            reason: self.reason,
+        }
+    }
+}
+
+impl<S, I> From<TransitionRejectedError<S, I>> for TransitionImpossibleError {
+    fn from(_: TransitionRejectedError<S, I>) -> Self {
+        TransitionImpossibleError
+    }
+}
+
+impl<S, I> fmt::Display for TransitionRejectedError<S, I>
+where
+    S: fmt::Debug,
+    I: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot perform a state transition from the state {:?} with the input {:?}: {}",
+            self.state, self.input, self.reason
+        )
+    }
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RejectionReason::NoTransition => write!(f, "no transition is defined"),
+            RejectionReason::GuardsRejected(guards) => {
+                write!(f, "rejected by the guards")?;
+                for (i, guard) in guards.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{separator}`{guard}`")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S, I> Error for TransitionRejectedError<S, I>
+where
+    S: fmt::Debug,
+    I: fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(feature = "std")]
+mod dfa;
+#[cfg(feature = "std")]
+mod machine;
+
+#[cfg(feature = "std")]
+pub use dfa::{Accepted, Dfa, DfaImpl, DfaTable};
+#[cfg(feature = "std")]
+pub use machine::NfaStateMachine;
+
+/// The definition of a nondeterministic finite automaton: an input may lead
+/// from a state to any number of states, and the states may have epsilon
+/// moves to other states, taken without consuming any input.
+///
+/// The automaton is run by [`NfaStateMachine`], tracking the set of the
+/// active states, or converted to a deterministic state machine by
+/// [`DfaTable::build`].
+pub trait NfaImpl {
+    /// The input alphabet.
+    type Input;
+    /// The set of possible states.
+    type State: Clone + Ord;
+    /// The initial state of the automaton. The automaton starts in this state
+    /// and in the states reachable from it by epsilon moves.
+    const INITIAL_STATE: Self::State;
+
+    /// The states reachable from the state with the input. No states means
+    /// there is no transition.
+    fn transition(
+        state: &Self::State,
+        input: &Self::Input,
+    ) -> impl IntoIterator<Item = Self::State>;
+
+    /// The states reachable from the state without consuming any input.
+    fn epsilon(_state: &Self::State) -> impl IntoIterator<Item = Self::State> {
+        []
+    }
+
+    /// Whether the automaton accepts the inputs consumed so far when the state
+    /// is active.
+    fn is_accepting(state: &Self::State) -> bool;
+}
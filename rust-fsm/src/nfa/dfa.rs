@@ -0,0 +1,200 @@
+use super::{
+    machine::{epsilon_closure, step},
+    NfaImpl,
+};
+use crate::StateMachineImpl;
+use core::{fmt, marker::PhantomData};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The transition table of the deterministic finite automaton equivalent to
+/// a nondeterministic one, built by the subset construction. Every state of
+/// the table is a set of the states of the nondeterministic automaton, and is
+/// identified by its index, `0` being the initial state.
+pub struct DfaTable<N: NfaImpl> {
+    inputs: Vec<N::Input>,
+    states: Vec<BTreeSet<N::State>>,
+    /// The transitions of the state `i` with the input `j` are at
+    /// `i * inputs.len() + j`
+    transitions: Vec<Option<usize>>,
+    accepting: Vec<bool>,
+}
+
+impl<N> DfaTable<N>
+where
+    N: NfaImpl,
+    N::Input: PartialEq,
+{
+    /// Builds the table for the given input alphabet, only containing the
+    /// states reachable from the initial state. There is no transition where
+    /// the nondeterministic automaton would have no active states.
+    pub fn build(inputs: impl IntoIterator<Item = N::Input>) -> Self {
+        let inputs = inputs.into_iter().collect::<Vec<_>>();
+        let initial_state = epsilon_closure::<N>(BTreeSet::from([N::INITIAL_STATE]));
+        let mut indices = BTreeMap::from([(initial_state.clone(), 0)]);
+        let mut states = vec![initial_state];
+        let mut transitions = Vec::new();
+
+        let mut next = 0;
+        while next < states.len() {
+            for input in &inputs {
+                let target = step::<N>(&states[next], input);
+                let transition = if target.is_empty() {
+                    None
+                } else {
+                    let index = *indices.entry(target.clone()).or_insert(states.len());
+                    if index == states.len() {
+                        states.push(target);
+                    }
+                    Some(index)
+                };
+                transitions.push(transition);
+            }
+            next += 1;
+        }
+
+        let accepting = states
+            .iter()
+            .map(|states| states.iter().any(N::is_accepting))
+            .collect();
+        Self {
+            inputs,
+            states,
+            transitions,
+            accepting,
+        }
+    }
+
+    /// Returns the number of the states.
+    // allow since the table is never empty, it always contains the initial
+    // state
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Returns the states of the nondeterministic automaton making up the
+    /// state of the table.
+    pub fn states(&self, state: usize) -> &BTreeSet<N::State> {
+        &self.states[state]
+    }
+
+    /// Whether the state of the table is accepting.
+    pub fn is_accepting(&self, state: usize) -> bool {
+        self.accepting[state]
+    }
+
+    /// Returns the state reached from the state of the table with the input,
+    /// if any. Inputs outside of the alphabet of the table have no
+    /// transitions.
+    pub fn next(&self, state: usize, input: &N::Input) -> Option<usize> {
+        let input = self.inputs.iter().position(|known| known == input)?;
+        self.transitions[state * self.inputs.len() + input]
+    }
+}
+
+impl<N> fmt::Debug for DfaTable<N>
+where
+    N: NfaImpl,
+    N::Input: fmt::Debug,
+    N::State: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DfaTable")
+            .field("inputs", &self.inputs)
+            .field("states", &self.states)
+            .field("transitions", &self.transitions)
+            .field("accepting", &self.accepting)
+            .finish()
+    }
+}
+
+/// Provides the table of a deterministic state machine built from a
+/// nondeterministic automaton, so it can be used through [`Dfa`]. The table
+/// is usually built once and kept in a static:
+///
+/// ```rust
+/// # use rust_fsm::{DfaImpl, DfaTable, NfaImpl};
+/// # use std::sync::OnceLock;
+/// #
+/// # #[derive(Clone, Copy, PartialEq)]
+/// # enum Input {
+/// #     A,
+/// #     B,
+/// # }
+/// #
+/// # struct Pattern;
+/// #
+/// # impl NfaImpl for Pattern {
+/// #     type Input = Input;
+/// #     type State = u8;
+/// #     const INITIAL_STATE: u8 = 0;
+/// #
+/// #     fn transition(state: &u8, input: &Input) -> impl IntoIterator<Item = u8> {
+/// #         match (state, input) {
+/// #             (0, Input::A) => vec![0, 1],
+/// #             (0, Input::B) => vec![0],
+/// #             (1, Input::B) => vec![2],
+/// #             _ => vec![],
+/// #         }
+/// #     }
+/// #
+/// #     fn is_accepting(state: &u8) -> bool {
+/// #         *state == 2
+/// #     }
+/// # }
+/// #
+/// # struct PatternDfa;
+/// #
+/// impl DfaImpl for PatternDfa {
+///     type Nfa = Pattern;
+///
+///     fn table() -> &'static DfaTable<Pattern> {
+///         static TABLE: OnceLock<DfaTable<Pattern>> = OnceLock::new();
+///         TABLE.get_or_init(|| DfaTable::build([Input::A, Input::B]))
+///     }
+/// }
+/// ```
+pub trait DfaImpl {
+    /// The nondeterministic automaton the table is built from.
+    type Nfa: NfaImpl + 'static;
+
+    /// Returns the table.
+    fn table() -> &'static DfaTable<Self::Nfa>;
+}
+
+/// The output of a [`Dfa`] entering an accepting state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Accepted;
+
+/// The deterministic state machine driven by the table of `D`, used with
+/// [`StateMachine`](crate::StateMachine). The states are the indices of the
+/// states of the table, and the output is [`Accepted`] on every transition
+/// to an accepting state.
+pub struct Dfa<D>(PhantomData<D>);
+
+impl<D> StateMachineImpl for Dfa<D>
+where
+    D: DfaImpl,
+    <D::Nfa as NfaImpl>::Input: PartialEq,
+{
+    type Input = <D::Nfa as NfaImpl>::Input;
+    type State = usize;
+    type Output = Accepted;
+    const INITIAL_STATE: Self::State = 0;
+
+    fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
+        D::table().next(*state, input)
+    }
+
+    fn output(state: &Self::State, input: &Self::Input) -> Option<Self::Output> {
+        Self::transition(state, input)
+            .filter(|state| D::table().is_accepting(*state))
+            .map(|_| Accepted)
+    }
+}
+
+impl<D> fmt::Debug for Dfa<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Dfa")
+    }
+}
@@ -0,0 +1,105 @@
+use super::NfaImpl;
+use crate::TransitionImpossibleError;
+use core::fmt;
+use std::collections::BTreeSet;
+
+/// Extends the set of the states with the states reachable from them by
+/// epsilon moves.
+pub(super) fn epsilon_closure<N: NfaImpl>(mut states: BTreeSet<N::State>) -> BTreeSet<N::State> {
+    let mut pending = states.iter().cloned().collect::<Vec<_>>();
+    while let Some(state) = pending.pop() {
+        for next in N::epsilon(&state) {
+            if !states.contains(&next) {
+                pending.push(next.clone());
+                states.insert(next);
+            }
+        }
+    }
+    states
+}
+
+/// The states reachable from the set of the states with the input, including
+/// the epsilon moves.
+pub(super) fn step<N: NfaImpl>(
+    states: &BTreeSet<N::State>,
+    input: &N::Input,
+) -> BTreeSet<N::State> {
+    let states = states
+        .iter()
+        .flat_map(|state| N::transition(state, input))
+        .collect();
+    epsilon_closure::<N>(states)
+}
+
+/// Runs a nondeterministic finite automaton by tracking the set of its active
+/// states.
+pub struct NfaStateMachine<N: NfaImpl> {
+    states: BTreeSet<N::State>,
+}
+
+impl<N> NfaStateMachine<N>
+where
+    N: NfaImpl,
+{
+    /// Create a new instance of this wrapper, active in the initial state and
+    /// in the states reachable from it by epsilon moves.
+    pub fn new() -> Self {
+        Self {
+            states: epsilon_closure::<N>(BTreeSet::from([N::INITIAL_STATE])),
+        }
+    }
+
+    /// Consumes the provided input, moving from every active state to all
+    /// states reachable with it. If no state is reachable, the active states
+    /// are kept and an error is returned.
+    pub fn consume(&mut self, input: &N::Input) -> Result<(), TransitionImpossibleError> {
+        let states = step::<N>(&self.states, input);
+        if states.is_empty() {
+            return Err(TransitionImpossibleError);
+        }
+        self.states = states;
+        Ok(())
+    }
+
+    /// Returns the active states.
+    pub fn states(&self) -> &BTreeSet<N::State> {
+        &self.states
+    }
+
+    /// Whether any of the active states is accepting.
+    pub fn is_accepting(&self) -> bool {
+        self.states.iter().any(N::is_accepting)
+    }
+}
+
+impl<N> Default for NfaStateMachine<N>
+where
+    N: NfaImpl,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N> Clone for NfaStateMachine<N>
+where
+    N: NfaImpl,
+{
+    fn clone(&self) -> Self {
+        Self {
+            states: self.states.clone(),
+        }
+    }
+}
+
+impl<N> fmt::Debug for NfaStateMachine<N>
+where
+    N: NfaImpl,
+    N::State: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NfaStateMachine")
+            .field("states", &self.states)
+            .finish()
+    }
+}
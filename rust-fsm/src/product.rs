@@ -0,0 +1,142 @@
+use crate::{RejectionReason, StateMachineImpl};
+use core::{fmt, marker::PhantomData};
+
+/// Decides whether a [`Product`] accepts an input from whether its state
+/// machines do.
+pub trait ProductMode {
+    /// Whether the product accepts the input.
+    fn accepts(a: bool, b: bool) -> bool;
+
+    /// Whether the state machines always accept the inputs accepted by the
+    /// product, so there is no need to evaluate their transitions to know
+    /// which of them moved. Returns `false` by default.
+    fn all_accept() -> bool {
+        false
+    }
+}
+
+/// The product accepts an input only if both state machines accept it. This
+/// is the default mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Both;
+
+impl ProductMode for Both {
+    fn accepts(a: bool, b: bool) -> bool {
+        a && b
+    }
+
+    fn all_accept() -> bool {
+        true
+    }
+}
+
+/// The product accepts an input if any state machine accepts it, the state
+/// machine rejecting it keeps its state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Either;
+
+impl ProductMode for Either {
+    fn accepts(a: bool, b: bool) -> bool {
+        a || b
+    }
+}
+
+/// The product of two state machines consuming the same inputs in lockstep.
+/// Its state is the pair of their states, and its output is the pair of their
+/// outputs, which is only present if any of them outputs something.
+///
+/// Whether an input is accepted is decided by the mode, [`Both`] or
+/// [`Either`]. The hooks of a state machine are called on the transitions it
+/// performs, from [`StateMachineImpl::on_transition`] of the product. With
+/// [`Either`], the transitions are evaluated again there to find out which
+/// state machines accepted the input, so their guards run twice.
+pub struct Product<A, B, M = Both>(PhantomData<(A, B, M)>);
+
+impl<A, B, M> StateMachineImpl for Product<A, B, M>
+where
+    A: StateMachineImpl,
+    B: StateMachineImpl<Input = A::Input>,
+    A::State: Clone,
+    B::State: Clone,
+    M: ProductMode,
+{
+    type Input = A::Input;
+    type State = (A::State, B::State);
+    type Output = (Option<A::Output>, Option<B::Output>);
+    const INITIAL_STATE: Self::State = (A::INITIAL_STATE, B::INITIAL_STATE);
+
+    fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
+        let a = A::transition(&state.0, input);
+        let b = B::transition(&state.1, input);
+        if !M::accepts(a.is_some(), b.is_some()) {
+            return None;
+        }
+        // The state machine rejecting the input keeps its state
+        Some((
+            a.unwrap_or_else(|| state.0.clone()),
+            b.unwrap_or_else(|| state.1.clone()),
+        ))
+    }
+
+    /// The pair of the outputs of the state machines. The one rejecting the
+    /// input has no output for it, so the transitions are not evaluated.
+    fn output(state: &Self::State, input: &Self::Input) -> Option<Self::Output> {
+        let outputs = (A::output(&state.0, input), B::output(&state.1, input));
+        let has_output = outputs.0.is_some() || outputs.1.is_some();
+        has_output.then_some(outputs)
+    }
+
+    fn step(
+        state: &Self::State,
+        input: &Self::Input,
+    ) -> Option<(Self::State, Option<Self::Output>)> {
+        let a = A::step(&state.0, input);
+        let b = B::step(&state.1, input);
+        if !M::accepts(a.is_some(), b.is_some()) {
+            return None;
+        }
+        let (a_state, a) = a.unwrap_or_else(|| (state.0.clone(), None));
+        let (b_state, b) = b.unwrap_or_else(|| (state.1.clone(), None));
+        let has_output = a.is_some() || b.is_some();
+        Some(((a_state, b_state), has_output.then_some((a, b))))
+    }
+
+    /// The reason of the state machine rejecting the input. If both of them
+    /// reject it, the first reason not being [`RejectionReason::NoTransition`].
+    fn rejection_reason(state: &Self::State, input: &Self::Input) -> RejectionReason {
+        if A::transition(&state.0, input).is_some() {
+            return B::rejection_reason(&state.1, input);
+        }
+        match A::rejection_reason(&state.0, input) {
+            RejectionReason::NoTransition if B::transition(&state.1, input).is_none() => {
+                B::rejection_reason(&state.1, input)
+            }
+            reason => reason,
+        }
+    }
+
+    fn on_transition(from: &Self::State, input: &Self::Input, to: &Self::State) {
+        let (a, b) = if M::all_accept() {
+            (true, true)
+        } else {
+            let a = A::transition(&from.0, input).is_some();
+            (a, !a || B::transition(&from.1, input).is_some())
+        };
+        if a {
+            A::on_exit(&from.0);
+            A::on_transition(&from.0, input, &to.0);
+            A::on_enter(&to.0);
+        }
+        if b {
+            B::on_exit(&from.1);
+            B::on_transition(&from.1, input, &to.1);
+            B::on_enter(&to.1);
+        }
+    }
+}
+
+impl<A, B, M> fmt::Debug for Product<A, B, M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Product")
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    History, NoHistory, NoRecorder, RejectionReason, StateMachine, StateMachineContextImpl,
+};
+use core::fmt;
+use std::{collections::VecDeque, error::Error};
+
+/// A driver around [`StateMachine`] processing events in the run-to-completion
+/// order: an event is fully processed (including the handling of its output)
+/// before the next one is taken from the queue.
+///
+/// External events are queued with [`QueuedStateMachine::push`]. The handler
+/// of the outputs may raise internal events, which take priority over the
+/// external ones. To guard against infinite loops of internal events, a single
+/// run consumes at most [`QueuedStateMachine::step_budget`] events.
+///
+/// The history `H` is the one of the wrapped [`StateMachine`], kept when the
+/// queue is created with `QueuedStateMachine::from(machine)`.
+pub struct QueuedStateMachine<T: StateMachineContextImpl, H = NoHistory> {
+    machine: StateMachine<T, NoRecorder, H>,
+    external: VecDeque<T::Input>,
+    internal: VecDeque<T::Input>,
+    step_budget: usize,
+}
+
+/// A handle passed to the output handler of [`QueuedStateMachine`] to raise
+/// internal events.
+#[derive(Debug)]
+pub struct Raise<'a, I> {
+    internal: &'a mut VecDeque<I>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An error returned by [`QueuedStateMachine::run`]. The events that were not
+/// processed yet stay queued.
+pub enum RunError<I> {
+    /// The state machine rejected an event, which is dropped from the queue.
+    Rejected {
+        /// The rejected event.
+        input: I,
+        /// Why the event was rejected.
+        reason: RejectionReason,
+    },
+    /// The step budget was exhausted, which usually means that internal
+    /// events raise each other in an infinite loop.
+    StepBudgetExceeded,
+}
+
+impl<T, H> QueuedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+    H: History<T>,
+{
+    /// The step budget used unless it is set with
+    /// [`QueuedStateMachine::with_step_budget`].
+    pub const DEFAULT_STEP_BUDGET: usize = 1024;
+
+    /// Create a new instance of this wrapper which encapsulates the initial
+    /// state and has no queued events.
+    pub fn new() -> Self
+    where
+        H: Default,
+    {
+        StateMachine::new().into()
+    }
+
+    /// Sets the maximum number of events consumed by a single run.
+    pub fn with_step_budget(mut self, step_budget: usize) -> Self {
+        self.step_budget = step_budget;
+        self
+    }
+
+    /// Returns the maximum number of events consumed by a single run.
+    pub fn step_budget(&self) -> usize {
+        self.step_budget
+    }
+
+    /// Queues an external event.
+    pub fn push(&mut self, input: T::Input) {
+        self.external.push_back(input);
+    }
+
+    /// Returns the number of queued events, both internal and external.
+    pub fn pending(&self) -> usize {
+        self.internal.len() + self.external.len()
+    }
+
+    /// Consumes the queued events using the provided context until there are
+    /// no more events left. Every output is passed to the handler, which may
+    /// raise internal events. Returns the number of consumed events.
+    pub fn run_with(
+        &mut self,
+        context: &mut T::Context,
+        mut handler: impl FnMut(T::Output, &mut Raise<'_, T::Input>),
+    ) -> Result<usize, RunError<T::Input>> {
+        let mut steps = 0;
+        while let Some(input) = self
+            .internal
+            .pop_front()
+            .or_else(|| self.external.pop_front())
+        {
+            if steps == self.step_budget {
+                self.internal.push_front(input);
+                return Err(RunError::StepBudgetExceeded);
+            }
+            steps += 1;
+
+            match self.machine.try_consume_with(context, &input) {
+                Ok(Some(output)) => handler(
+                    output,
+                    &mut Raise {
+                        internal: &mut self.internal,
+                    },
+                ),
+                Ok(None) => {}
+                Err(err) => {
+                    let reason = err.reason;
+                    return Err(RunError::Rejected { input, reason });
+                }
+            }
+        }
+        Ok(steps)
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> &T::State {
+        self.machine.state()
+    }
+
+    /// Returns the wrapped state machine, dropping the queued events.
+    pub fn into_inner(self) -> StateMachine<T, NoRecorder, H> {
+        self.machine
+    }
+}
+
+impl<T, H> QueuedStateMachine<T, H>
+where
+    T: StateMachineContextImpl<Context = ()>,
+    H: History<T>,
+{
+    /// The same as [`QueuedStateMachine::run_with`], but without a context.
+    pub fn run(
+        &mut self,
+        handler: impl FnMut(T::Output, &mut Raise<'_, T::Input>),
+    ) -> Result<usize, RunError<T::Input>> {
+        self.run_with(&mut (), handler)
+    }
+}
+
+impl<T, H> Default for QueuedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+    H: History<T> + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, H> From<StateMachine<T, NoRecorder, H>> for QueuedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+    H: History<T>,
+{
+    fn from(machine: StateMachine<T, NoRecorder, H>) -> Self {
+        Self {
+            machine,
+            external: VecDeque::new(),
+            internal: VecDeque::new(),
+            step_budget: Self::DEFAULT_STEP_BUDGET,
+        }
+    }
+}
+
+impl<T, H> fmt::Debug for QueuedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+    H: History<T>,
+    T::State: fmt::Debug,
+    T::Input: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("QueuedStateMachine")
+            .field("state", self.state())
+            .field("internal", &self.internal)
+            .field("external", &self.external)
+            .field("step_budget", &self.step_budget)
+            .finish()
+    }
+}
+
+impl<I> Raise<'_, I> {
+    /// Raises an internal event. Internal events are consumed in the order
+    /// they were raised, before any external event.
+    pub fn raise(&mut self, input: I) {
+        self.internal.push_back(input);
+    }
+}
+
+impl<I> fmt::Display for RunError<I>
+where
+    I: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::Rejected { input, reason } => {
+                write!(f, "the event {input:?} was rejected: {reason}")
+            }
+            RunError::StepBudgetExceeded => write!(f, "the step budget is exceeded"),
+        }
+    }
+}
+
+impl<I> Error for RunError<I>
+where
+    I: fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
@@ -0,0 +1,256 @@
+use crate::StateMachineContextImpl;
+use core::fmt;
+
+/// A single performed state transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransitionRecord<S, I, O> {
+    /// The state before the transition.
+    pub from: S,
+    /// The consumed input.
+    pub input: I,
+    /// The state after the transition.
+    pub to: S,
+    /// The output given by the transition.
+    pub output: Option<O>,
+}
+
+/// The record type used by the recorders of the state machine `T`.
+pub type RecordOf<T> = TransitionRecord<
+    <T as StateMachineContextImpl>::State,
+    <T as StateMachineContextImpl>::Input,
+    <T as StateMachineContextImpl>::Output,
+>;
+
+/// Records the transitions performed by a [`StateMachine`](crate::StateMachine),
+/// e.g. to find out how the machine ended up in its current state.
+pub trait TransitionRecorder<T: StateMachineContextImpl> {
+    /// Called on every performed transition, after the transition hook.
+    fn record(
+        &mut self,
+        from: &T::State,
+        input: &T::Input,
+        to: &T::State,
+        output: Option<&T::Output>,
+    );
+}
+
+/// The recorder that records nothing. This is the default recorder of
+/// [`StateMachine`](crate::StateMachine).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoRecorder;
+
+impl<T> TransitionRecorder<T> for NoRecorder
+where
+    T: StateMachineContextImpl,
+{
+    fn record(&mut self, _: &T::State, _: &T::Input, _: &T::State, _: Option<&T::Output>) {}
+}
+
+/// The recorder that keeps the last `N` transitions in a ring buffer, without
+/// allocating. The oldest records are overwritten once the buffer is full.
+pub struct RingBufferRecorder<T: StateMachineContextImpl, const N: usize> {
+    records: [Option<RecordOf<T>>; N],
+    next: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> RingBufferRecorder<T, N>
+where
+    T: StateMachineContextImpl,
+{
+    /// Creates an empty recorder.
+    pub fn new() -> Self {
+        Self {
+            records: [const { None }; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns an iterator over the records, from the oldest to the newest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &RecordOf<T>> + '_ {
+        let start = self.next + N - self.len;
+        (start..start + self.len).filter_map(move |index| self.records[index % N].as_ref())
+    }
+
+    /// Returns the number of the kept records.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all records.
+    pub fn clear(&mut self) {
+        self.records.iter_mut().for_each(|record| *record = None);
+        self.next = 0;
+        self.len = 0;
+    }
+}
+
+impl<T, const N: usize> TransitionRecorder<T> for RingBufferRecorder<T, N>
+where
+    T: StateMachineContextImpl,
+    T::State: Clone,
+    T::Input: Clone,
+    T::Output: Clone,
+{
+    fn record(
+        &mut self,
+        from: &T::State,
+        input: &T::Input,
+        to: &T::State,
+        output: Option<&T::Output>,
+    ) {
+        if N == 0 {
+            return;
+        }
+        self.records[self.next] = Some(TransitionRecord {
+            from: from.clone(),
+            input: input.clone(),
+            to: to.clone(),
+            output: output.cloned(),
+        });
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+}
+
+impl<T, const N: usize> Default for RingBufferRecorder<T, N>
+where
+    T: StateMachineContextImpl,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Clone for RingBufferRecorder<T, N>
+where
+    T: StateMachineContextImpl,
+    RecordOf<T>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            records: self.records.clone(),
+            next: self.next,
+            len: self.len,
+        }
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for RingBufferRecorder<T, N>
+where
+    T: StateMachineContextImpl,
+    RecordOf<T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// The recorder that keeps all transitions in a [`Vec`].
+#[cfg(feature = "std")]
+pub struct VecRecorder<T: StateMachineContextImpl> {
+    records: Vec<RecordOf<T>>,
+}
+
+#[cfg(feature = "std")]
+impl<T> VecRecorder<T>
+where
+    T: StateMachineContextImpl,
+{
+    /// Creates an empty recorder.
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+        }
+    }
+
+    /// Returns an iterator over the records, from the oldest to the newest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &RecordOf<T>> + '_ {
+        self.records.iter()
+    }
+
+    /// Returns the records, from the oldest to the newest.
+    pub fn records(&self) -> &[RecordOf<T>] {
+        &self.records
+    }
+
+    /// Returns the number of the kept records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if there are no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Removes all records.
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> TransitionRecorder<T> for VecRecorder<T>
+where
+    T: StateMachineContextImpl,
+    T::State: Clone,
+    T::Input: Clone,
+    T::Output: Clone,
+{
+    fn record(
+        &mut self,
+        from: &T::State,
+        input: &T::Input,
+        to: &T::State,
+        output: Option<&T::Output>,
+    ) {
+        self.records.push(TransitionRecord {
+            from: from.clone(),
+            input: input.clone(),
+            to: to.clone(),
+            output: output.cloned(),
+        });
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Default for VecRecorder<T>
+where
+    T: StateMachineContextImpl,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Clone for VecRecorder<T>
+where
+    T: StateMachineContextImpl,
+    RecordOf<T>: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            records: self.records.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> fmt::Debug for VecRecorder<T>
+where
+    T: StateMachineContextImpl,
+    RecordOf<T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
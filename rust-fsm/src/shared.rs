@@ -0,0 +1,195 @@
+use crate::{
+    History, NoHistory, NoRecorder, StateMachine, StateMachineContextImpl,
+    TransitionImpossibleError,
+};
+use core::fmt;
+use std::{
+    error::Error,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError},
+};
+
+/// A thread-safe handle to a [`StateMachine`] that can be cloned and shared
+/// between threads. All clones refer to the same state machine.
+///
+/// A panic in a hook or a closure does not make the state machine unusable:
+/// the state is only replaced once the new state is computed.
+///
+/// The history `H` is the one of the wrapped [`StateMachine`]: the state
+/// machines entering composite states through their history are shared with
+/// `SharedStateMachine::from(machine)`, which keeps the history of the
+/// `StateMachine` type generated by the DSL.
+pub struct SharedStateMachine<T: StateMachineContextImpl, H = NoHistory> {
+    machine: Arc<Mutex<StateMachine<T, NoRecorder, H>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An error returned by [`SharedStateMachine::consume_if`].
+pub enum ConsumeIfError {
+    /// The current state does not satisfy the condition, so the input was not
+    /// consumed.
+    ConditionNotMet,
+    /// The state transition is impossible given the current combination of
+    /// state and input.
+    TransitionImpossible,
+}
+
+impl<T, H> SharedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+    H: History<T>,
+{
+    /// Create a new instance of this wrapper which encapsulates the initial
+    /// state.
+    pub fn new() -> Self
+    where
+        H: Default,
+    {
+        Self::from_state(T::INITIAL_STATE)
+    }
+
+    /// Create a new instance of this wrapper which encapsulates the given
+    /// state.
+    pub fn from_state(state: T::State) -> Self
+    where
+        H: Default,
+    {
+        StateMachine::from_state(state).into()
+    }
+
+    /// Locks the state machine for exclusive access, e.g. to consume several
+    /// inputs without other threads interleaving.
+    pub fn lock(&self) -> MutexGuard<'_, StateMachine<T, NoRecorder, H>> {
+        self.machine.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The same as [`StateMachine::consume_with`].
+    pub fn consume_with(
+        &self,
+        context: &mut T::Context,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        self.lock().consume_with(context, input)
+    }
+
+    /// Consumes the provided input using the provided context only if the
+    /// current state satisfies the condition. Checking the condition and
+    /// consuming the input happen atomically.
+    pub fn consume_if_with(
+        &self,
+        condition: impl FnOnce(&T::State) -> bool,
+        context: &mut T::Context,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, ConsumeIfError> {
+        let mut machine = self.lock();
+        if !condition(machine.state()) {
+            return Err(ConsumeIfError::ConditionNotMet);
+        }
+        Ok(machine.consume_with(context, input)?)
+    }
+
+    /// Returns a snapshot of the current state.
+    pub fn state(&self) -> T::State
+    where
+        T::State: Clone,
+    {
+        self.lock().state().clone()
+    }
+}
+
+impl<T, H> SharedStateMachine<T, H>
+where
+    T: StateMachineContextImpl<Context = ()>,
+    H: History<T>,
+{
+    /// The same as [`StateMachine::consume`].
+    pub fn consume(
+        &self,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        self.consume_with(&mut (), input)
+    }
+
+    /// Consumes the provided input only if the current state satisfies the
+    /// condition, e.g. `|state| *state == State::Open`. Checking the condition
+    /// and consuming the input happen atomically.
+    pub fn consume_if(
+        &self,
+        condition: impl FnOnce(&T::State) -> bool,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, ConsumeIfError> {
+        self.consume_if_with(condition, &mut (), input)
+    }
+}
+
+impl<T, H> Clone for SharedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+{
+    fn clone(&self) -> Self {
+        Self {
+            machine: Arc::clone(&self.machine),
+        }
+    }
+}
+
+impl<T, H> Default for SharedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+    H: History<T> + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, H> From<StateMachine<T, NoRecorder, H>> for SharedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+{
+    fn from(machine: StateMachine<T, NoRecorder, H>) -> Self {
+        Self {
+            machine: Arc::new(Mutex::new(machine)),
+        }
+    }
+}
+
+impl<T, H> fmt::Debug for SharedStateMachine<T, H>
+where
+    T: StateMachineContextImpl,
+    H: History<T>,
+    T::State: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Do not block when the state machine is locked by the current thread
+        let mut debug = f.debug_struct("SharedStateMachine");
+        match self.machine.try_lock() {
+            Ok(machine) => debug.field("state", machine.state()),
+            Err(TryLockError::Poisoned(err)) => debug.field("state", err.get_ref().state()),
+            Err(TryLockError::WouldBlock) => debug.field("state", &format_args!("<locked>")),
+        };
+        debug.finish()
+    }
+}
+
+impl From<TransitionImpossibleError> for ConsumeIfError {
+    fn from(_: TransitionImpossibleError) -> Self {
+        ConsumeIfError::TransitionImpossible
+    }
+}
+
+impl fmt::Display for ConsumeIfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConsumeIfError::ConditionNotMet => {
+                write!(f, "the current state does not satisfy the condition")
+            }
+            ConsumeIfError::TransitionImpossible => TransitionImpossibleError.fmt(f),
+        }
+    }
+}
+
+impl Error for ConsumeIfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
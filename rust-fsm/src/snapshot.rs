@@ -0,0 +1,248 @@
+use crate::StateMachineContextImpl;
+#[cfg(feature = "std")]
+use crate::{History, StateMachine, TransitionRecorder};
+#[cfg(feature = "std")]
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+/// Names the states of a versioned state machine definition. The DSL
+/// implements this trait for the generated `State` enums, the version is set
+/// with `#[state_machine(version(...))]` and is `0` by default.
+pub trait SnapshotImpl: StateMachineContextImpl {
+    /// The version of the state machine definition, which should be bumped
+    /// every time the states are renamed, split or removed.
+    const VERSION: u32;
+    /// Returns the name of the given state.
+    fn state_name(state: &Self::State) -> &'static str;
+    /// Returns the state with the given name, or `None` if there is no such
+    /// state or the state carries data. Only the states returned by this
+    /// function can be recorded in snapshots.
+    fn state_from_name(name: &str) -> Option<Self::State>;
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// A persistent snapshot of a state machine, recording the name of its state
+/// and the version of the definition it was taken with. Restored with
+/// [`Migrations`].
+pub struct Snapshot {
+    /// The version of the state machine definition.
+    pub version: u32,
+    /// The name of the state.
+    pub state: String,
+}
+
+/// A registry of migrations mapping the states of older versions of a state
+/// machine definition to the states of the current one.
+///
+/// A state of a snapshot is restored by the first matching migration, the
+/// migrations for a specific version taking priority over the ones for all
+/// versions. Unmapped states are restored by name, as long as they still
+/// exist in the current definition and do not carry data.
+#[cfg(feature = "std")]
+pub struct Migrations<T: SnapshotImpl> {
+    migrations: Vec<Migration<T::State>>,
+}
+
+#[cfg(feature = "std")]
+struct Migration<S> {
+    version: Option<u32>,
+    from: String,
+    to: S,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An error returned when a [`Snapshot`] cannot be restored.
+pub enum MigrationError {
+    /// The state of the snapshot does not exist in the current definition (or
+    /// carries data) and there is no migration for it.
+    UnmappedState {
+        /// The version of the snapshot.
+        version: u32,
+        /// The name of the unmapped state.
+        state: String,
+    },
+    /// The snapshot was taken with a newer version of the definition.
+    UnsupportedVersion {
+        /// The version of the snapshot.
+        version: u32,
+        /// The version of the current definition.
+        current: u32,
+    },
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An error returned when a snapshot of the current state cannot be taken, as
+/// the state carries data, which a [`Snapshot`] cannot record.
+pub struct SnapshotError {
+    /// The name of the state.
+    pub state: &'static str,
+}
+
+#[cfg(feature = "std")]
+impl<T> Migrations<T>
+where
+    T: SnapshotImpl,
+{
+    /// Creates an empty registry, restoring the states by name only.
+    pub fn new() -> Self {
+        Self {
+            migrations: Vec::new(),
+        }
+    }
+
+    /// Maps the state with the given name in any version to the given state.
+    pub fn rename(mut self, from: &str, to: T::State) -> Self {
+        self.migrations.push(Migration {
+            version: None,
+            from: from.to_owned(),
+            to,
+        });
+        self
+    }
+
+    /// Maps the state with the given name in the given version to the given
+    /// state.
+    pub fn rename_in(mut self, version: u32, from: &str, to: T::State) -> Self {
+        self.migrations.push(Migration {
+            version: Some(version),
+            from: from.to_owned(),
+            to,
+        });
+        self
+    }
+
+    /// Restores the state of the given snapshot. Fails on the snapshots of
+    /// newer versions and on unmapped states.
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<T::State, MigrationError>
+    where
+        T::State: Clone,
+    {
+        if snapshot.version > T::VERSION {
+            return Err(MigrationError::UnsupportedVersion {
+                version: snapshot.version,
+                current: T::VERSION,
+            });
+        }
+        let find = |version| {
+            self.migrations
+                .iter()
+                .find(|migration| migration.version == version && migration.from == snapshot.state)
+        };
+        if let Some(migration) = find(Some(snapshot.version)).or_else(|| find(None)) {
+            return Ok(migration.to.clone());
+        }
+        T::state_from_name(&snapshot.state).ok_or_else(|| MigrationError::UnmappedState {
+            version: snapshot.version,
+            state: snapshot.state.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Default for Migrations<T>
+where
+    T: SnapshotImpl,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> fmt::Debug for Migrations<T>
+where
+    T: SnapshotImpl,
+    T::State: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.migrations
+                    .iter()
+                    .map(|migration| (migration.version, &migration.from, &migration.to)),
+            )
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T, R, H> StateMachine<T, R, H>
+where
+    T: SnapshotImpl,
+    R: TransitionRecorder<T>,
+    H: History<T>,
+{
+    /// Takes a snapshot of the current state. Fails if the state cannot be
+    /// restored by its name, i.e. it carries data: only the name of the state
+    /// is recorded, so the data would be lost.
+    pub fn snapshot(&self) -> Result<Snapshot, SnapshotError> {
+        let state = T::state_name(self.state());
+        if T::state_from_name(state).is_none() {
+            return Err(SnapshotError { state });
+        }
+        Ok(Snapshot {
+            version: T::VERSION,
+            state: state.to_owned(),
+        })
+    }
+
+    /// Create a new instance of this wrapper which encapsulates the state of
+    /// the given snapshot, migrated with the given migrations.
+    pub fn from_snapshot(
+        snapshot: &Snapshot,
+        migrations: &Migrations<T>,
+    ) -> Result<Self, MigrationError>
+    where
+        T::State: Clone,
+        R: Default,
+        H: Default,
+    {
+        migrations.restore(snapshot).map(Self::from_state)
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationError::UnmappedState { version, state } => write!(
+                f,
+                "the state `{state}` of the version {version} is not mapped to any state"
+            ),
+            MigrationError::UnsupportedVersion { version, current } => write!(
+                f,
+                "the version {version} is newer than the current version {current}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the state `{}` carries data, which cannot be recorded in a snapshot",
+            self.state
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for MigrationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{
+    History, NoHistory, NoRecorder, StateMachine, StateMachineContextImpl,
+    TransitionImpossibleError,
+};
+use core::{cell::Cell, fmt, time::Duration};
+
+/// Describes the timeouts of the states: the input fired once the machine
+/// stays in a state for the given duration. The DSL implements this trait for
+/// the state machines with timeouts (e.g., `Open => after(5s) => HalfOpen`).
+pub trait TimeoutImpl: StateMachineContextImpl {
+    /// Returns the timeout of the given state along with the input fired once
+    /// it expires, or `None` if the state has no timeout.
+    fn timeout(state: &Self::State) -> Option<(Duration, Self::Input)>;
+}
+
+/// The source of time of [`TimedStateMachine`]. Returns the time elapsed since
+/// an arbitrary but fixed moment, which must never go backwards.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Duration;
+}
+
+impl<C> Clock for &C
+where
+    C: Clock + ?Sized,
+{
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+/// The clock measuring the time elapsed since its creation with
+/// [`std::time::Instant`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    /// Creates a clock starting at the current moment.
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock that only moves when it is advanced manually, which allows testing
+/// timeouts without waiting for them.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: Cell<Duration>,
+}
+
+impl MockClock {
+    /// Creates a clock starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+/// A wrapper around [`StateMachine`] scheduling the timeouts of the states
+/// against a [`Clock`]. The timeout of a state is scheduled every time the
+/// machine enters the state, including the transitions looping back to the
+/// same state.
+///
+/// Expired timeouts are fired by [`TimedStateMachine::poll_timeout`], which
+/// is expected to be called periodically, e.g. after sleeping for
+/// [`TimedStateMachine::time_left`].
+///
+/// The history `H` of the wrapped [`StateMachine`] must be set for the state
+/// machines entering composite states through their history (e.g.,
+/// `TimedStateMachine<player::Impl, SystemClock, SubstateHistory<player::Impl>>`).
+pub struct TimedStateMachine<T: TimeoutImpl, C: Clock, H = NoHistory> {
+    machine: StateMachine<T, NoRecorder, H>,
+    clock: C,
+    deadline: Option<Duration>,
+}
+
+impl<T, C, H> TimedStateMachine<T, C, H>
+where
+    T: TimeoutImpl,
+    C: Clock,
+    H: History<T>,
+{
+    /// Create a new instance of this wrapper which encapsulates the initial
+    /// state and schedules its timeout.
+    pub fn new(clock: C) -> Self
+    where
+        H: Default,
+    {
+        Self::from_state(T::INITIAL_STATE, clock)
+    }
+
+    /// Create a new instance of this wrapper which encapsulates the given
+    /// state and schedules its timeout.
+    pub fn from_state(state: T::State, clock: C) -> Self
+    where
+        H: Default,
+    {
+        let deadline = Self::deadline(&state, clock.now());
+        Self {
+            machine: StateMachine::from_state(state),
+            clock,
+            deadline,
+        }
+    }
+
+    /// The same as [`StateMachine::consume_with`]. Schedules the timeout of
+    /// the new state.
+    pub fn consume_with(
+        &mut self,
+        context: &mut T::Context,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        let now = self.clock.now();
+        self.consume_at(context, input, now)
+    }
+
+    /// Fires the timeout of the current state using the provided context if
+    /// it has expired. Returns `None` if there is no expired timeout.
+    ///
+    /// The timeout of the new state is scheduled from the moment the previous
+    /// timeout expired, so this function should be called until it returns
+    /// `None` to catch up with the clock.
+    pub fn poll_timeout_with(
+        &mut self,
+        context: &mut T::Context,
+    ) -> Option<Result<Option<T::Output>, TransitionImpossibleError>> {
+        let deadline = self
+            .deadline
+            .filter(|deadline| *deadline <= self.clock.now())?;
+        // The deadline is only set for the states with a timeout
+        let (_, input) = T::timeout(self.machine.state())?;
+        let res = self.consume_at(context, &input, deadline);
+        if res.is_err() {
+            self.deadline = None;
+        }
+        Some(res)
+    }
+
+    /// Returns the time left until the timeout of the current state expires,
+    /// or `None` if the current state has no timeout.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_sub(self.clock.now()))
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> &T::State {
+        self.machine.state()
+    }
+
+    /// Returns the clock.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    fn consume_at(
+        &mut self,
+        context: &mut T::Context,
+        input: &T::Input,
+        now: Duration,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        let output = self.machine.consume_with(context, input)?;
+        self.deadline = Self::deadline(self.machine.state(), now);
+        Ok(output)
+    }
+
+    fn deadline(state: &T::State, now: Duration) -> Option<Duration> {
+        T::timeout(state).map(|(timeout, _)| now + timeout)
+    }
+}
+
+impl<T, C, H> TimedStateMachine<T, C, H>
+where
+    T: TimeoutImpl<Context = ()>,
+    C: Clock,
+    H: History<T>,
+{
+    /// The same as [`StateMachine::consume`]. Schedules the timeout of the new
+    /// state.
+    pub fn consume(
+        &mut self,
+        input: &T::Input,
+    ) -> Result<Option<T::Output>, TransitionImpossibleError> {
+        self.consume_with(&mut (), input)
+    }
+
+    /// The same as [`TimedStateMachine::poll_timeout_with`], but without a
+    /// context.
+    pub fn poll_timeout(&mut self) -> Option<Result<Option<T::Output>, TransitionImpossibleError>> {
+        self.poll_timeout_with(&mut ())
+    }
+}
+
+impl<T, C, H> fmt::Debug for TimedStateMachine<T, C, H>
+where
+    T: TimeoutImpl,
+    H: History<T>,
+    T::State: fmt::Debug,
+    C: Clock + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TimedStateMachine")
+            .field("state", self.state())
+            .field("clock", &self.clock)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
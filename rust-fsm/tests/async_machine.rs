@@ -0,0 +1,165 @@
+/// Test for asynchronous state machines awaiting guards and outputs
+use rust_fsm::*;
+use std::{
+    collections::HashSet,
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+/// A waker doing nothing, as the executor below polls the future in a loop.
+fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(std::ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    // Safety: the functions of the vtable ignore the data pointer
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+}
+
+/// A minimal executor polling the future until it is ready.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// A future that is pending once before completing, like an I/O call.
+async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|_| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[derive(Debug, Default)]
+pub struct Tokens {
+    valid: HashSet<u32>,
+    used: Vec<u32>,
+}
+
+impl Tokens {
+    async fn is_valid(&self, token: u32) -> bool {
+        yield_now().await;
+        self.valid.contains(&token)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    Granted(u32),
+    Denied,
+}
+
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    #[state_machine(output(crate::Output), context(crate::Tokens))]
+    door(Locked)
+
+    use super::{Output, Tokens};
+
+    Locked => {
+        Badge(u32) if async |tokens: &Tokens, token: &u32| tokens.is_valid(*token).await => Unlocked [async |tokens: &mut Tokens, token: &u32| {
+            super::yield_now().await;
+            tokens.used.push(*token);
+            Output::Granted(*token)
+        }],
+        Badge(u32) => Locked [Denied]
+    },
+    Unlocked(Close) => Locked
+}
+
+#[test]
+fn async_guards_and_outputs() {
+    let mut tokens = Tokens {
+        valid: HashSet::from([7]),
+        used: Vec::new(),
+    };
+    let mut machine = door::StateMachine::new();
+
+    // The futures can be spawned on multi-threaded executors
+    fn assert_send<T: Send>(_: &T) {}
+    let future = machine.consume_with(&mut tokens, &door::Input::Badge(3));
+    assert_send(&future);
+    let res = block_on(future);
+    assert_eq!(res, Ok(Some(Output::Denied)));
+    assert_eq!(machine.state(), &door::State::Locked);
+
+    let res = block_on(machine.consume_with(&mut tokens, &door::Input::Badge(7)));
+    assert_eq!(res, Ok(Some(Output::Granted(7))));
+    assert_eq!(machine.state(), &door::State::Unlocked);
+    assert_eq!(tokens.used, [7]);
+
+    let err = block_on(machine.try_consume_with(&mut tokens, &door::Input::Badge(7))).unwrap_err();
+    assert_eq!(err.state, &door::State::Unlocked);
+    assert_eq!(err.reason, RejectionReason::NoTransition);
+
+    block_on(machine.consume_with(&mut tokens, &door::Input::Close)).unwrap();
+    assert_eq!(machine.state(), &door::State::Locked);
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    counter(Idle)
+
+    Idle(Start) => Counting(0),
+    Counting(u32) => {
+        Add(u32) => Counting(async |count: &u32, amount: &u32| {
+            super::yield_now().await;
+            count + amount
+        }),
+        Stop => Idle
+    },
+    Counting(u32) => on_enter [|count: &u32| assert!(*count < 1000)]
+}
+
+#[test]
+fn async_data_constructors_without_context() {
+    let mut machine = counter::StateMachine::new();
+
+    block_on(machine.consume(&counter::Input::Start)).unwrap();
+    block_on(machine.consume(&counter::Input::Add(5))).unwrap();
+    block_on(machine.consume(&counter::Input::Add(10))).unwrap();
+    assert_eq!(machine.state(), &counter::State::Counting(15));
+
+    assert!(block_on(machine.consume(&counter::Input::Start)).is_err());
+    block_on(machine.consume(&counter::Input::Stop)).unwrap();
+    assert_eq!(machine.state(), &counter::State::Idle);
+}
+
+struct Echo;
+
+impl AsyncStateMachineImpl for Echo {
+    type Input = u32;
+    type State = u32;
+    type Output = u32;
+    type Context = ();
+    const INITIAL_STATE: Self::State = 0;
+
+    async fn step(state: &u32, input: &u32, _: &mut ()) -> Option<(u32, Option<u32>)> {
+        yield_now().await;
+        (input > state).then_some((*input, Some(*state)))
+    }
+}
+
+#[test]
+fn async_without_dsl() {
+    let mut machine: AsyncStateMachine<Echo> = AsyncStateMachine::new();
+    assert_eq!(block_on(machine.consume(&3)), Ok(Some(0)));
+    assert_eq!(block_on(machine.consume(&5)), Ok(Some(3)));
+    assert!(block_on(machine.consume(&4)).is_err());
+    assert_eq!(machine.state(), &5);
+}
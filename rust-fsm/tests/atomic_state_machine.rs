@@ -0,0 +1,204 @@
+/// Test for the lock-free state machine storing fieldless states in an atomic
+use rust_fsm::*;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+state_machine! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(u8)]
+    circuit_breaker(Closed)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [SetupTimer]
+    }
+}
+
+type AtomicCircuitBreaker = AtomicStateMachine<circuit_breaker::Impl>;
+
+#[test]
+fn atomic_state_roundtrip() {
+    use circuit_breaker::State;
+
+    for state in [State::Closed, State::HalfOpen, State::Open] {
+        assert_eq!(State::from_u8(state.to_u8()), Some(state));
+    }
+    assert_eq!(State::from_u8(u8::MAX), None);
+}
+
+#[test]
+fn atomic_circuit_breaker() {
+    let machine = AtomicCircuitBreaker::new();
+
+    let res = machine.consume(&circuit_breaker::Input::Unsuccessful);
+    assert_eq!(res, Ok(Some(circuit_breaker::Output::SetupTimer)));
+    assert_eq!(machine.state(), circuit_breaker::State::Open);
+
+    let res = machine.consume(&circuit_breaker::Input::Successful);
+    assert_eq!(res, Err(TransitionImpossibleError));
+    assert_eq!(machine.state(), circuit_breaker::State::Open);
+
+    machine
+        .consume(&circuit_breaker::Input::TimerTriggered)
+        .unwrap();
+    machine
+        .consume(&circuit_breaker::Input::Successful)
+        .unwrap();
+    assert_eq!(machine.state(), circuit_breaker::State::Closed);
+    assert_eq!(machine.retries(), 0);
+    assert_eq!(
+        format!("{machine:?}"),
+        "AtomicStateMachine { state: Closed, retries: 0 }"
+    );
+}
+
+state_machine! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(u8)]
+    toggle(Off)
+
+    Off(Flip) => On,
+    On(Flip) => Off
+}
+
+#[test]
+fn atomic_concurrent_transitions() {
+    const THREADS: usize = 8;
+    const FLIPS: usize = 1000;
+
+    let machine = Arc::new(AtomicStateMachine::<toggle::Impl>::new());
+    let threads = (0..THREADS)
+        .map(|_| {
+            let machine = machine.clone();
+            std::thread::spawn(move || {
+                for _ in 0..FLIPS {
+                    machine.consume(&toggle::Input::Flip).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Every flip is applied exactly once, no matter how many retries happened
+    assert_eq!(machine.state(), toggle::State::Off);
+}
+
+thread_local! {
+    static CALLS: std::cell::RefCell<Vec<&'static str>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+fn call(name: &'static str) {
+    CALLS.with(|calls| calls.borrow_mut().push(name));
+}
+
+/// Records the calls of its functions and hooks
+pub struct Recorded;
+
+impl StateMachineImpl for Recorded {
+    type Input = toggle::Input;
+    type State = toggle::State;
+    type Output = ();
+    const INITIAL_STATE: Self::State = toggle::State::Off;
+
+    fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
+        call("transition");
+        toggle::Impl::transition(state, input)
+    }
+
+    fn output(_: &Self::State, _: &Self::Input) -> Option<Self::Output> {
+        call("output");
+        Some(())
+    }
+
+    fn step(state: &Self::State, input: &Self::Input) -> Option<(Self::State, Option<()>)> {
+        call("step");
+        toggle::Impl::transition(state, input).map(|state| (state, Some(())))
+    }
+
+    fn on_exit(_: &Self::State) {
+        call("on_exit");
+    }
+
+    fn on_transition(_: &Self::State, _: &Self::Input, _: &Self::State) {
+        call("on_transition");
+    }
+
+    fn on_enter(_: &Self::State) {
+        call("on_enter");
+    }
+}
+
+#[test]
+fn atomic_hooks_order() {
+    let machine = AtomicStateMachine::<Recorded>::new();
+    assert_eq!(machine.consume(&toggle::Input::Flip), Ok(Some(())));
+    assert_eq!(
+        CALLS.with(|calls| calls.take()),
+        [
+            "transition",
+            "output",
+            "on_exit",
+            "on_transition",
+            "on_enter"
+        ]
+    );
+
+    // The hooks run in the same order as by the non-atomic state machine
+    let mut machine = StateMachine::<Recorded>::new();
+    machine.consume(&toggle::Input::Flip).unwrap();
+    assert_eq!(
+        CALLS.with(|calls| calls.take()),
+        ["step", "on_exit", "on_transition", "on_enter"]
+    );
+}
+
+static OUTPUTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts the evaluations of its output function
+pub struct Counted;
+
+impl StateMachineImpl for Counted {
+    type Input = toggle::Input;
+    type State = toggle::State;
+    type Output = ();
+    const INITIAL_STATE: Self::State = toggle::State::Off;
+
+    fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
+        toggle::Impl::transition(state, input)
+    }
+
+    fn output(_: &Self::State, _: &Self::Input) -> Option<Self::Output> {
+        OUTPUTS.fetch_add(1, Ordering::Relaxed);
+        Some(())
+    }
+}
+
+#[test]
+fn atomic_output_once_per_transition() {
+    const THREADS: usize = 8;
+    const FLIPS: usize = 1000;
+
+    let machine = Arc::new(AtomicStateMachine::<Counted>::new());
+    let threads = (0..THREADS)
+        .map(|_| {
+            let machine = machine.clone();
+            std::thread::spawn(move || {
+                for _ in 0..FLIPS {
+                    machine.consume(&toggle::Input::Flip).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // The retried attempts do not compute the output
+    assert_eq!(OUTPUTS.load(Ordering::Relaxed), THREADS * FLIPS);
+}
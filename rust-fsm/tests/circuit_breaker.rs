@@ -1,8 +1,8 @@
+#![cfg(feature = "std")]
 /// A dummy implementation of the Circuit Breaker pattern to demonstrate
 /// capabilities of this library.
 /// https://martinfowler.com/bliki/CircuitBreaker.html
 use rust_fsm::*;
-use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 #[derive(Debug)]
@@ -67,9 +67,9 @@ fn circuit_breaker() {
     let machine: StateMachine<CircuitBreakerMachine> = StateMachine::new();
 
     // Unsuccessful request
-    let machine = Arc::new(Mutex::new(machine));
+    let machine = SharedStateMachine::from(machine);
     {
-        let mut lock = machine.lock().unwrap();
+        let mut lock = machine.lock();
         let res = lock.consume(&CircuitBreakerInput::Unsuccessful).unwrap();
         assert_eq!(res, Some(CircuitBreakerOutputSetTimer));
         assert_eq!(lock.state(), &CircuitBreakerState::Open);
@@ -79,7 +79,7 @@ fn circuit_breaker() {
     let machine_wait = machine.clone();
     std::thread::spawn(move || {
         std::thread::sleep(Duration::from_millis(500));
-        let mut lock = machine_wait.lock().unwrap();
+        let mut lock = machine_wait.lock();
         let res = lock.consume(&CircuitBreakerInput::TimerTriggered).unwrap();
         assert_eq!(res, None);
         assert_eq!(lock.state(), &CircuitBreakerState::HalfOpen);
@@ -89,7 +89,7 @@ fn circuit_breaker() {
     let machine_try = machine.clone();
     std::thread::spawn(move || {
         std::thread::sleep(Duration::from_millis(100));
-        let mut lock = machine_try.lock().unwrap();
+        let mut lock = machine_try.lock();
         let res = lock.consume(&CircuitBreakerInput::Successful);
         assert!(matches!(res, Err(TransitionImpossibleError)));
         assert_eq!(lock.state(), &CircuitBreakerState::Open);
@@ -98,7 +98,7 @@ fn circuit_breaker() {
     // Test if the circit breaker was actually closed
     std::thread::sleep(Duration::from_millis(700));
     {
-        let mut lock = machine.lock().unwrap();
+        let mut lock = machine.lock();
         let res = lock.consume(&CircuitBreakerInput::Successful).unwrap();
         assert_eq!(res, None);
         assert_eq!(lock.state(), &CircuitBreakerState::Closed);
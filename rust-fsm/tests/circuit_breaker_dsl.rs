@@ -1,8 +1,8 @@
+#![cfg(feature = "std")]
 /// A dummy implementation of the Circuit Breaker pattern to demonstrate
 /// capabilities of its library DSL for defining finite state machines.
 /// https://martinfowler.com/bliki/CircuitBreaker.html
 use rust_fsm::*;
-use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 state_machine! {
@@ -21,9 +21,9 @@ fn circit_breaker_dsl() {
     let machine = circuit_breaker::StateMachine::new();
 
     // Unsuccessful request
-    let machine = Arc::new(Mutex::new(machine));
+    let machine = SharedStateMachine::from(machine);
     {
-        let mut lock = machine.lock().unwrap();
+        let mut lock = machine.lock();
         let res = lock.consume(&circuit_breaker::Input::Unsuccessful).unwrap();
         assert!(matches!(res, Some(circuit_breaker::Output::SetupTimer)));
         assert!(matches!(lock.state(), &circuit_breaker::State::Open));
@@ -33,7 +33,7 @@ fn circit_breaker_dsl() {
     let machine_wait = machine.clone();
     std::thread::spawn(move || {
         std::thread::sleep(Duration::from_millis(500));
-        let mut lock = machine_wait.lock().unwrap();
+        let mut lock = machine_wait.lock();
         let res = lock
             .consume(&circuit_breaker::Input::TimerTriggered)
             .unwrap();
@@ -45,7 +45,7 @@ fn circit_breaker_dsl() {
     let machine_try = machine.clone();
     std::thread::spawn(move || {
         std::thread::sleep(Duration::from_millis(100));
-        let mut lock = machine_try.lock().unwrap();
+        let mut lock = machine_try.lock();
         let res = lock.consume(&circuit_breaker::Input::Successful);
         assert!(matches!(res, Err(TransitionImpossibleError)));
         assert!(matches!(lock.state(), &circuit_breaker::State::Open));
@@ -54,7 +54,7 @@ fn circit_breaker_dsl() {
     // Test if the circit breaker was actually closed
     std::thread::sleep(Duration::from_millis(700));
     {
-        let mut lock = machine.lock().unwrap();
+        let mut lock = machine.lock();
         let res = lock.consume(&circuit_breaker::Input::Successful).unwrap();
         assert!(res.is_none());
         assert!(matches!(lock.state(), &circuit_breaker::State::Closed));
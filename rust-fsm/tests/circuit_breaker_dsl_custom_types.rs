@@ -1,8 +1,8 @@
+#![cfg(feature = "std")]
 /// A dummy implementation of the Circuit Breaker pattern to demonstrate
 /// capabilities of its library DSL for defining finite state machines.
 /// https://martinfowler.com/bliki/CircuitBreaker.html
 use rust_fsm::*;
-use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 pub enum Input {
@@ -38,9 +38,9 @@ fn circit_breaker_dsl() {
     let machine = circuit_breaker::StateMachine::new();
 
     // Unsuccessful request
-    let machine = Arc::new(Mutex::new(machine));
+    let machine = SharedStateMachine::from(machine);
     {
-        let mut lock = machine.lock().unwrap();
+        let mut lock = machine.lock();
         let res = lock.consume(&Input::Unsuccessful).unwrap();
         assert!(matches!(res, Some(Output::SetupTimer)));
         assert!(matches!(lock.state(), &State::Open));
@@ -50,7 +50,7 @@ fn circit_breaker_dsl() {
     let machine_wait = machine.clone();
     std::thread::spawn(move || {
         std::thread::sleep(Duration::from_millis(500));
-        let mut lock = machine_wait.lock().unwrap();
+        let mut lock = machine_wait.lock();
         let res = lock.consume(&Input::TimerTriggered).unwrap();
         assert!(res.is_none());
         assert!(matches!(lock.state(), &State::HalfOpen));
@@ -60,7 +60,7 @@ fn circit_breaker_dsl() {
     let machine_try = machine.clone();
     std::thread::spawn(move || {
         std::thread::sleep(Duration::from_millis(100));
-        let mut lock = machine_try.lock().unwrap();
+        let mut lock = machine_try.lock();
         let res = lock.consume(&Input::Successful);
         assert!(matches!(res, Err(TransitionImpossibleError)));
         assert!(matches!(lock.state(), &State::Open));
@@ -69,7 +69,7 @@ fn circit_breaker_dsl() {
     // Test if the circit breaker was actually closed
     std::thread::sleep(Duration::from_millis(700));
     {
-        let mut lock = machine.lock().unwrap();
+        let mut lock = machine.lock();
         let res = lock.consume(&Input::Successful).unwrap();
         assert!(res.is_none());
         assert!(matches!(lock.state(), &State::Closed));
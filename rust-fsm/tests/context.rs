@@ -0,0 +1,135 @@
+/// Test for state machines with an extended state passed through `consume_with`
+use rust_fsm::*;
+
+#[derive(Debug, Default)]
+pub struct Failures {
+    count: u32,
+    threshold: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    SetupTimer,
+    Failed(u32),
+}
+
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    #[state_machine(output(crate::Output), context(crate::Failures))]
+    circuit_breaker(Closed)
+
+    use super::{Failures, Output};
+
+    Closed => {
+        Unsuccessful if |failures: &Failures| failures.count + 1 >= failures.threshold => Open [|failures: &mut Failures| {
+            failures.count = 0;
+            Output::SetupTimer
+        }],
+        Unsuccessful => Closed [|failures: &mut Failures| {
+            failures.count += 1;
+            Output::Failed(failures.count)
+        }],
+        Successful => Closed [|failures: &mut Failures| {
+            failures.count = 0;
+            Output::Failed(0)
+        }]
+    },
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [|_: &mut Failures| Output::SetupTimer]
+    }
+}
+
+#[test]
+fn context_counts_failures() {
+    let mut failures = Failures {
+        count: 0,
+        threshold: 3,
+    };
+    let mut machine = circuit_breaker::StateMachine::new();
+
+    let res = machine.consume_with(&mut failures, &circuit_breaker::Input::Unsuccessful);
+    assert_eq!(res, Ok(Some(Output::Failed(1))));
+    let res = machine.consume_with(&mut failures, &circuit_breaker::Input::Unsuccessful);
+    assert_eq!(res, Ok(Some(Output::Failed(2))));
+    assert_eq!(machine.state(), &circuit_breaker::State::Closed);
+
+    let res = machine.consume_with(&mut failures, &circuit_breaker::Input::Unsuccessful);
+    assert_eq!(res, Ok(Some(Output::SetupTimer)));
+    assert_eq!(machine.state(), &circuit_breaker::State::Open);
+    assert_eq!(failures.count, 0);
+
+    let res = machine.consume_with(&mut failures, &circuit_breaker::Input::Successful);
+    assert!(matches!(res, Err(TransitionImpossibleError)));
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(context(u32))]
+    limiter(Accepting)
+
+    Accepting => {
+        Request(u32) if |limit: &u32, size: &u32| size <= limit => Accepting,
+        Request(u32) => Rejecting(|limit: &u32, size: &u32| size - limit)
+    },
+    Rejecting(u32) => {
+        Request(u32) if |limit: &u32, excess: &u32, size: &u32| size + excess <= *limit => Accepting,
+        Reset => Accepting
+    }
+}
+
+#[test]
+fn context_in_guards_and_state_data() {
+    let mut limit = 10;
+    let mut machine = limiter::StateMachine::new();
+
+    machine
+        .consume_with(&mut limit, &limiter::Input::Request(5))
+        .unwrap();
+    assert_eq!(machine.state(), &limiter::State::Accepting);
+
+    machine
+        .consume_with(&mut limit, &limiter::Input::Request(12))
+        .unwrap();
+    assert_eq!(machine.state(), &limiter::State::Rejecting(2));
+
+    assert!(machine
+        .consume_with(&mut limit, &limiter::Input::Request(9))
+        .is_err());
+
+    limit = 20;
+    machine
+        .consume_with(&mut limit, &limiter::Input::Request(9))
+        .unwrap();
+    assert_eq!(machine.state(), &limiter::State::Accepting);
+}
+
+#[derive(Debug)]
+struct Counter;
+
+impl StateMachineContextImpl for Counter {
+    type Input = ();
+    type State = ();
+    type Output = u32;
+    type Context = u32;
+    const INITIAL_STATE: Self::State = ();
+
+    fn transition_with(_: &(), _: &(), _: &u32) -> Option<()> {
+        Some(())
+    }
+
+    fn output_with(_: &(), _: &(), count: &mut u32) -> Option<u32> {
+        *count += 1;
+        Some(*count)
+    }
+}
+
+#[test]
+fn context_without_dsl() {
+    let mut count = 0;
+    let mut machine: StateMachine<Counter> = StateMachine::new();
+    assert_eq!(machine.consume_with(&mut count, &()), Ok(Some(1)));
+    assert_eq!(machine.consume_with(&mut count, &()), Ok(Some(2)));
+    assert_eq!(count, 2);
+}
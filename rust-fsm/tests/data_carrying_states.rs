@@ -0,0 +1,101 @@
+/// Test for states carrying data in the state_machine! macro
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    retry(Idle)
+
+    Idle(Start) => Running,
+    Running => {
+        Fail => Failed(1),
+        Done(u32) => Finished(|result: &u32| *result)
+    },
+    Failed(u32) => {
+        Fail if |attempts: &u32| *attempts < 3 => Failed(|attempts: &u32| attempts + 1),
+        Fail => GaveUp [MaxRetriesExceeded],
+        Tick => Failed,
+        Retry => Running,
+    },
+    Finished(u32) => {},
+    GaveUp(Reset) => Idle
+}
+
+#[test]
+fn tuple_state_data() {
+    let mut machine = retry::StateMachine::new();
+    machine.consume(&retry::Input::Start).unwrap();
+
+    machine.consume(&retry::Input::Fail).unwrap();
+    assert_eq!(machine.state(), &retry::State::Failed(1));
+
+    // Self-loop without a data constructor keeps the data
+    machine.consume(&retry::Input::Tick).unwrap();
+    assert_eq!(machine.state(), &retry::State::Failed(1));
+
+    machine.consume(&retry::Input::Fail).unwrap();
+    machine.consume(&retry::Input::Fail).unwrap();
+    assert_eq!(machine.state(), &retry::State::Failed(3));
+
+    // The guard receives the state data and rejects the self-loop
+    let res = machine.consume(&retry::Input::Fail);
+    assert_eq!(res, Ok(Some(retry::Output::MaxRetriesExceeded)));
+    assert_eq!(machine.state(), &retry::State::GaveUp);
+
+    machine.consume(&retry::Input::Reset).unwrap();
+    machine.consume(&retry::Input::Start).unwrap();
+    machine.consume(&retry::Input::Done(42)).unwrap();
+    assert_eq!(machine.state(), &retry::State::Finished(42));
+    assert!(machine.consume(&retry::Input::Start).is_err());
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    breaker(Closed)
+
+    Closed(Unsuccessful(u64)) => Open(|since: &u64| (*since, 1)),
+    Open { since: u64, failures: u32 } => {
+        Unsuccessful(u64) => Open(|since: &u64, failures: &u32, _now: &u64| (*since, failures + 1)),
+        Tick(u64) if |since: &u64, _: &u32, now: &u64| now - since >= 10 => HalfOpen,
+        Tick(u64) => Open
+    },
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful(u64) => Open(|now: &u64| (*now, 1))
+    }
+}
+
+#[test]
+fn struct_state_data() {
+    let mut machine = breaker::StateMachine::new();
+
+    machine.consume(&breaker::Input::Unsuccessful(100)).unwrap();
+    assert_eq!(
+        machine.state(),
+        &breaker::State::Open {
+            since: 100,
+            failures: 1
+        }
+    );
+
+    machine.consume(&breaker::Input::Unsuccessful(105)).unwrap();
+    machine.consume(&breaker::Input::Tick(105)).unwrap();
+    assert_eq!(
+        machine.state(),
+        &breaker::State::Open {
+            since: 100,
+            failures: 2
+        }
+    );
+
+    machine.consume(&breaker::Input::Tick(110)).unwrap();
+    assert_eq!(machine.state(), &breaker::State::HalfOpen);
+
+    machine.consume(&breaker::Input::Unsuccessful(120)).unwrap();
+    assert_eq!(
+        machine.state(),
+        &breaker::State::Open {
+            since: 120,
+            failures: 1
+        }
+    );
+}
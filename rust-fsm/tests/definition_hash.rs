@@ -0,0 +1,126 @@
+/// Test for the fingerprints of state machine definitions
+use rust_fsm::*;
+
+state_machine! {
+    circuit_breaker(Closed)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [SetupTimer]
+    }
+}
+
+// The same definition, formatted differently and with the transitions in a
+// different order
+state_machine! {
+    reformatted(Closed)
+
+    HalfOpen => {
+        Unsuccessful   =>   Open [ SetupTimer ],
+        Successful => Closed, // comment
+    },
+    Open(TimerTriggered) => HalfOpen,
+    Closed(Unsuccessful) => Open [SetupTimer]
+}
+
+state_machine! {
+    other_output(Closed)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [ResetTimer]
+    }
+}
+
+state_machine! {
+    other_transition(Closed)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Closed [SetupTimer]
+    }
+}
+
+state_machine! {
+    other_initial_state(Open)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [SetupTimer]
+    }
+}
+
+state_machine! {
+    guarded(Idle)
+
+    Idle => {
+        Coin(u32) if |amount: &u32| *amount >= 100 => Unlocked,
+        Coin(u32) => Idle,
+    },
+    Unlocked(Push) => Idle
+}
+
+state_machine! {
+    reformatted_guard(Idle)
+
+    Unlocked(Push) => Idle,
+    Idle => {
+        Coin(u32) if |amount:&u32|*amount>=100 => Unlocked,
+        Coin(u32) => Idle
+    }
+}
+
+state_machine! {
+    other_guard(Idle)
+
+    Idle => {
+        Coin(u32) if |amount: &u32| *amount >= 50 => Unlocked,
+        Coin(u32) => Idle,
+    },
+    Unlocked(Push) => Idle
+}
+
+state_machine! {
+    other_input_data(Idle)
+
+    Idle => {
+        Coin(u64) if |amount: &u64| *amount >= 100 => Unlocked,
+        Coin(u64) => Idle,
+    },
+    Unlocked(Push) => Idle
+}
+
+#[test]
+fn formatting_does_not_change_hash() {
+    assert_eq!(
+        circuit_breaker::DEFINITION_HASH,
+        reformatted::DEFINITION_HASH
+    );
+    assert_eq!(guarded::DEFINITION_HASH, reformatted_guard::DEFINITION_HASH);
+}
+
+#[test]
+fn graph_changes_change_hash() {
+    let hashes = [
+        circuit_breaker::DEFINITION_HASH,
+        other_output::DEFINITION_HASH,
+        other_transition::DEFINITION_HASH,
+        other_initial_state::DEFINITION_HASH,
+        guarded::DEFINITION_HASH,
+        other_guard::DEFINITION_HASH,
+        other_input_data::DEFINITION_HASH,
+    ];
+    for (i, a) in hashes.iter().enumerate() {
+        for b in &hashes[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+}
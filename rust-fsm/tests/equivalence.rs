@@ -0,0 +1,144 @@
+#![cfg(feature = "std")]
+/// Test for checking the equivalence of state machine implementations
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    door(Closed)
+
+    Closed => {
+        Push => Open,
+        Lock => Locked,
+    },
+    Open => {
+        Close => Closed [Slammed],
+        Push => Wide,
+    },
+    Wide => {
+        Close => Closed [Slammed],
+        Push => Wide,
+    },
+    Locked(Unlock) => Closed,
+}
+
+// All inputs of the DSL state machine
+const INPUTS: &[door::Input] = <door::Impl as IntrospectImpl>::INPUTS;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Door {
+    Closed,
+    Open,
+    Locked,
+}
+
+/// The door with the equivalent `Open` and `Wide` states merged
+pub struct MinimalDoor;
+
+impl StateMachineImpl for MinimalDoor {
+    type Input = door::Input;
+    type State = Door;
+    type Output = door::Output;
+    const INITIAL_STATE: Self::State = Door::Closed;
+
+    fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
+        match (state, input) {
+            (Door::Closed, door::Input::Push) | (Door::Open, door::Input::Push) => Some(Door::Open),
+            (Door::Closed, door::Input::Lock) => Some(Door::Locked),
+            (Door::Open, door::Input::Close) | (Door::Locked, door::Input::Unlock) => {
+                Some(Door::Closed)
+            }
+            _ => None,
+        }
+    }
+
+    fn output(state: &Self::State, input: &Self::Input) -> Option<Self::Output> {
+        match (state, input) {
+            (Door::Open, door::Input::Close) => Some(door::Output::Slammed),
+            _ => None,
+        }
+    }
+}
+
+/// The door that can also be locked while open
+pub struct LeakyDoor;
+
+impl StateMachineImpl for LeakyDoor {
+    type Input = door::Input;
+    type State = Door;
+    type Output = door::Output;
+    const INITIAL_STATE: Self::State = Door::Closed;
+
+    fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
+        match (state, input) {
+            (Door::Open, door::Input::Lock) => Some(Door::Locked),
+            _ => MinimalDoor::transition(state, input),
+        }
+    }
+
+    fn output(state: &Self::State, input: &Self::Input) -> Option<Self::Output> {
+        MinimalDoor::output(state, input)
+    }
+}
+
+/// The door that does not slam when closed after being pushed twice
+pub struct QuietDoor;
+
+impl StateMachineImpl for QuietDoor {
+    type Input = door::Input;
+    type State = (Door, bool);
+    type Output = door::Output;
+    const INITIAL_STATE: Self::State = (Door::Closed, false);
+
+    fn transition(state: &Self::State, input: &Self::Input) -> Option<Self::State> {
+        let pushed = matches!((state, input), ((Door::Open, _), door::Input::Push));
+        MinimalDoor::transition(&state.0, input).map(|door| (door, pushed))
+    }
+
+    fn output(state: &Self::State, input: &Self::Input) -> Option<Self::Output> {
+        match state {
+            (_, true) => None,
+            (door, false) => MinimalDoor::output(door, input),
+        }
+    }
+}
+
+#[test]
+fn equivalent_implementations() {
+    assert_eq!(
+        check_equivalence::<door::Impl, MinimalDoor>(INPUTS),
+        Equivalence::Equivalent
+    );
+    assert_eq!(
+        check_equivalence::<MinimalDoor, door::Impl>(INPUTS),
+        Equivalence::Equivalent
+    );
+}
+
+#[test]
+fn diverging_acceptance() {
+    assert_eq!(
+        check_equivalence::<door::Impl, LeakyDoor>(INPUTS),
+        Equivalence::Diverges(vec![door::Input::Push, door::Input::Lock])
+    );
+}
+
+#[test]
+fn diverging_outputs() {
+    assert_eq!(
+        check_equivalence::<door::Impl, QuietDoor>(INPUTS),
+        Equivalence::Diverges(vec![
+            door::Input::Push,
+            door::Input::Push,
+            door::Input::Close
+        ])
+    );
+}
+
+#[test]
+fn inputs_limit_exploration() {
+    // Locking is never tried
+    assert_eq!(
+        check_equivalence::<door::Impl, LeakyDoor>(&[door::Input::Push, door::Input::Close]),
+        Equivalence::Equivalent
+    );
+}
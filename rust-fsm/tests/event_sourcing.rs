@@ -0,0 +1,166 @@
+#![cfg(feature = "std")]
+/// Test for rebuilding state machines from a log of inputs
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    order(Placed)
+
+    Placed(Pay(u32)) => Paid [Charged],
+    Paid => {
+        Ship => Shipped,
+        Cancel => Cancelled [Refunded]
+    }
+}
+
+// The next version of the order definition no longer allows cancelling paid
+// orders
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    order_v2(Placed)
+
+    Placed(Pay(u32)) => Paid [Charged],
+    Paid(Ship) => Shipped
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    player(Stopped)
+
+    Stopped(Play) => Active,
+    Active(Playing) {
+        Playing(Pause) => Paused,
+        Paused(Play) => Playing,
+    },
+    Active(Interrupt) => Interrupted,
+    Interrupted(Resume) => Active(H),
+}
+
+pub struct Failures {
+    count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failed(u32);
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(output(crate::Failed), context(crate::Failures))]
+    circuit_breaker(Closed)
+
+    use super::{Failed, Failures};
+
+    Closed => {
+        Unsuccessful if |failures: &Failures| failures.count >= 2 => Open,
+        Unsuccessful => Closed [|failures: &mut Failures| {
+            failures.count += 1;
+            Failed(failures.count)
+        }]
+    },
+    Open(TimerTriggered) => Closed
+}
+
+#[test]
+fn replay_inputs() {
+    let inputs = [order::Input::Pay(10), order::Input::Ship];
+    let machine = order::StateMachine::replay(&inputs).unwrap();
+    assert_eq!(machine.state(), &order::State::Shipped);
+
+    let machine = order::StateMachine::replay(inputs).unwrap();
+    assert_eq!(machine.state(), &order::State::Shipped);
+
+    let machine = order::StateMachine::replay(Vec::<order::Input>::new()).unwrap();
+    assert_eq!(machine.state(), &order::State::Placed);
+}
+
+#[test]
+fn replay_reports_first_rejected_input() {
+    let inputs = [
+        order_v2::Input::Pay(10),
+        order_v2::Input::Ship,
+        order_v2::Input::Ship,
+    ];
+    let err = order_v2::StateMachine::replay(&inputs).unwrap_err();
+    assert_eq!(
+        err,
+        ReplayError {
+            index: 2,
+            state: order_v2::State::Shipped,
+            input: &order_v2::Input::Ship,
+            reason: RejectionReason::NoTransition,
+        }
+    );
+}
+
+#[test]
+fn restore_after_restart() {
+    let mut machine = EventSourcedStateMachine::<order::Impl, _>::new(Vec::new());
+    assert_eq!(
+        machine.consume(&order::Input::Pay(10)),
+        Ok(Some(order::Output::Charged))
+    );
+    // Rejected inputs are not logged
+    assert!(machine.consume(&order::Input::Pay(20)).is_err());
+    machine.consume(&order::Input::Cancel).unwrap();
+    assert_eq!(
+        machine.log(),
+        &vec![order::Input::Pay(10), order::Input::Cancel]
+    );
+
+    // The service restarts, only the log survives
+    let (_, log) = machine.into_parts();
+    let machine = EventSourcedStateMachine::<order::Impl, _>::restore(log).unwrap();
+    assert_eq!(machine.state(), &order::State::Cancelled);
+    assert_eq!(
+        format!("{machine:?}"),
+        "EventSourcedStateMachine { state: Cancelled, log: [Pay(10), Cancel] }"
+    );
+
+    // The restored log contains an input the definition no longer accepts
+    let log = vec![order_v2::Input::Pay(10), order_v2::Input::Pay(20)];
+    let err = EventSourcedStateMachine::<order_v2::Impl, _>::restore(log).unwrap_err();
+    assert_eq!(err.index, 1);
+    assert_eq!(err.state, order_v2::State::Paid);
+    assert_eq!(err.input, order_v2::Input::Pay(20));
+}
+
+#[test]
+fn restore_history() {
+    type EventSourcedPlayer =
+        EventSourcedStateMachine<player::Impl, Vec<player::Input>, SubstateHistory<player::Impl>>;
+
+    let mut machine = EventSourcedPlayer::new(Vec::new());
+    machine.consume(&player::Input::Play).unwrap();
+    machine.consume(&player::Input::Pause).unwrap();
+    machine.consume(&player::Input::Interrupt).unwrap();
+
+    // The remembered substate is rebuilt from the log
+    let (_, log) = machine.into_parts();
+    let mut machine = EventSourcedPlayer::restore(log).unwrap();
+    machine.consume(&player::Input::Resume).unwrap();
+    assert_eq!(
+        machine.state(),
+        &player::State::Active(player::ActiveState::Paused)
+    );
+}
+
+#[test]
+fn replay_context() {
+    let log = vec![circuit_breaker::Input::Unsuccessful; 3];
+
+    // The outputs counting the failures are replayed, so the guard opens the
+    // circuit breaker on the third failure
+    let mut failures = Failures { count: 0 };
+    let mut machine =
+        EventSourcedStateMachine::<circuit_breaker::Impl, _>::restore_with(&mut failures, log)
+            .unwrap();
+    assert_eq!(failures.count, 2);
+    assert_eq!(machine.state(), &circuit_breaker::State::Open);
+
+    machine
+        .consume_with(&mut failures, &circuit_breaker::Input::TimerTriggered)
+        .unwrap();
+    let res = machine.consume_with(&mut failures, &circuit_breaker::Input::Unsuccessful);
+    assert_eq!(res, Ok(None));
+    assert_eq!(machine.log().len(), 5);
+}
@@ -0,0 +1,160 @@
+/// Test for composite states with nested substates
+use rust_fsm::*;
+use std::cell::RefCell;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    connection(Disconnected)
+
+    Disconnected(Connect) => Connected,
+    Connected(Handshake) {
+        Handshake(Accepted) => Ready,
+        Ready(Send(u32)) => Busy(|size: &u32| *size),
+        Busy(u32) => {
+            Done => Ready,
+            // Overrides the transition of `Connected`
+            Disconnect => Disconnected [Aborted],
+        },
+        Paused(Resume) => Ready,
+    },
+    // Handled by all substates of `Connected`
+    Connected => {
+        Disconnect => Disconnected,
+        Pause => Paused,
+    }
+}
+
+#[test]
+fn enter_initial_substate() {
+    let mut machine = connection::StateMachine::new();
+    assert_eq!(machine.state(), &connection::State::Disconnected);
+
+    machine.consume(&connection::Input::Connect).unwrap();
+    assert_eq!(
+        machine.state(),
+        &connection::State::Connected(connection::ConnectedState::Handshake)
+    );
+
+    machine.consume(&connection::Input::Accepted).unwrap();
+    machine.consume(&connection::Input::Send(3)).unwrap();
+    assert_eq!(
+        machine.state(),
+        &connection::State::Connected(connection::ConnectedState::Busy(3))
+    );
+}
+
+#[test]
+fn inherited_transitions() {
+    let mut machine = connection::StateMachine::from_state(connection::State::Connected(
+        connection::ConnectedState::Ready,
+    ));
+    machine.consume(&connection::Input::Pause).unwrap();
+    assert_eq!(
+        machine.state(),
+        &connection::State::Connected(connection::ConnectedState::Paused)
+    );
+    machine.consume(&connection::Input::Resume).unwrap();
+
+    assert_eq!(machine.consume(&connection::Input::Disconnect), Ok(None));
+    assert_eq!(machine.state(), &connection::State::Disconnected);
+
+    // Transitions of the composite states do not apply outside of them
+    assert!(machine.consume(&connection::Input::Pause).is_err());
+}
+
+#[test]
+fn innermost_transition_first() {
+    let mut machine = connection::StateMachine::from_state(connection::State::Connected(
+        connection::ConnectedState::Busy(1),
+    ));
+    assert_eq!(
+        machine.consume(&connection::Input::Disconnect),
+        Ok(Some(connection::Output::Aborted))
+    );
+    assert_eq!(machine.state(), &connection::State::Disconnected);
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    player(Stopped)
+
+    use super::log;
+
+    Stopped(Play) => Active,
+    Active(Playing) {
+        Playing(Pause) => Paused,
+        Paused(Play) => Playing,
+        Playing(Next) => Playing,
+        Buffering(Loaded) => Playing,
+        // The nested composite state is entered through its initial substate
+        Playing(Seek) => Seeking,
+        Seeking(Locating) {
+            Locating(Found) => Seeking,
+            Locating(Fail) => Buffering,
+        },
+        Seeking => on_enter [|| log("enter Seeking")],
+        Seeking => on_exit [|| log("exit Seeking")],
+        Playing => on_enter [|| log("enter Playing")],
+    },
+    Active(Stop) => Stopped [Halted],
+    Active => on_enter [|| log("enter Active")],
+    Active => on_exit [|| log("exit Active")],
+    Stopped => on_exit [|| log("exit Stopped")],
+}
+
+thread_local! {
+    static LOG: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+fn log(message: &'static str) {
+    LOG.with(|log| log.borrow_mut().push(message));
+}
+
+fn take_log() -> Vec<&'static str> {
+    LOG.with(|log| log.take())
+}
+
+#[test]
+fn nested_composite_states() {
+    let mut machine = player::StateMachine::new();
+    machine.consume(&player::Input::Play).unwrap();
+    assert_eq!(
+        take_log(),
+        ["exit Stopped", "enter Active", "enter Playing"]
+    );
+
+    machine.consume(&player::Input::Seek).unwrap();
+    assert_eq!(
+        machine.state(),
+        &player::State::Active(player::ActiveState::Seeking(player::SeekingState::Locating))
+    );
+    assert_eq!(take_log(), ["enter Seeking"]);
+
+    // Staying in the composite state does not call its hooks
+    machine.consume(&player::Input::Found).unwrap();
+    assert_eq!(take_log(), Vec::<&str>::new());
+
+    machine.consume(&player::Input::Fail).unwrap();
+    assert_eq!(
+        machine.state(),
+        &player::State::Active(player::ActiveState::Buffering)
+    );
+    assert_eq!(take_log(), ["exit Seeking"]);
+
+    // Leaving the nested composite states calls the exit hooks from the
+    // innermost one
+    machine.consume(&player::Input::Loaded).unwrap();
+    machine.consume(&player::Input::Seek).unwrap();
+    take_log();
+    machine.consume(&player::Input::Stop).unwrap();
+    assert_eq!(take_log(), ["exit Seeking", "exit Active"]);
+    assert_eq!(machine.state(), &player::State::Stopped);
+}
+
+#[test]
+fn rejection_in_nested_state() {
+    let mut machine = player::StateMachine::new();
+    machine.consume(&player::Input::Play).unwrap();
+    let err = machine.try_consume(&player::Input::Found).unwrap_err();
+    assert_eq!(err.reason, RejectionReason::NoTransition);
+}
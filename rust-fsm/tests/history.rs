@@ -0,0 +1,125 @@
+/// Test for the history pseudo-states of composite states
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    player(Stopped)
+
+    Stopped(Play) => Active,
+    Active(Playing) {
+        Playing(Pause) => Paused,
+        Paused(Play) => Playing,
+        Playing(Seek) => Seeking,
+        Seeking(Locating) {
+            Locating(Found) => Buffering(|| 10),
+            Buffering(u32) => {
+                Loaded => Seeking [Restarted],
+            },
+        },
+    },
+    Active => {
+        Stop => Stopped,
+        Interrupt => Interrupted,
+    },
+    Interrupted => {
+        Resume => Active(H),
+        ResumeExactly => Active(H*),
+        Restart => Active,
+    }
+}
+
+fn active(state: player::ActiveState) -> player::State {
+    player::State::Active(state)
+}
+
+fn seeking(state: player::SeekingState) -> player::State {
+    active(player::ActiveState::Seeking(state))
+}
+
+#[test]
+fn shallow_history() {
+    let mut machine = player::StateMachine::new();
+    machine.consume(&player::Input::Play).unwrap();
+    machine.consume(&player::Input::Pause).unwrap();
+    machine.consume(&player::Input::Interrupt).unwrap();
+    assert_eq!(machine.state(), &player::State::Interrupted);
+
+    machine.consume(&player::Input::Resume).unwrap();
+    assert_eq!(machine.state(), &active(player::ActiveState::Paused));
+
+    // Without the history, the initial substate is entered
+    machine.consume(&player::Input::Interrupt).unwrap();
+    machine.consume(&player::Input::Restart).unwrap();
+    assert_eq!(machine.state(), &active(player::ActiveState::Playing));
+}
+
+#[test]
+fn shallow_history_enters_initial_nested_substate() {
+    let mut machine = player::StateMachine::from_state(seeking(player::SeekingState::Locating));
+    machine.consume(&player::Input::Found).unwrap();
+    machine.consume(&player::Input::Interrupt).unwrap();
+    machine.consume(&player::Input::Resume).unwrap();
+    assert_eq!(machine.state(), &seeking(player::SeekingState::Locating));
+}
+
+#[test]
+fn deep_history() {
+    let mut machine = player::StateMachine::from_state(seeking(player::SeekingState::Locating));
+    machine.consume(&player::Input::Found).unwrap();
+    machine.consume(&player::Input::Interrupt).unwrap();
+    machine.consume(&player::Input::ResumeExactly).unwrap();
+    assert_eq!(
+        machine.state(),
+        &seeking(player::SeekingState::Buffering(10))
+    );
+    assert_eq!(
+        machine.consume(&player::Input::Loaded),
+        Ok(Some(player::Output::Restarted))
+    );
+}
+
+#[test]
+fn empty_history_enters_initial_substate() {
+    let mut machine = player::StateMachine::from_state(player::State::Interrupted);
+    machine.consume(&player::Input::ResumeExactly).unwrap();
+    assert_eq!(machine.state(), &active(player::ActiveState::Playing));
+
+    machine.consume(&player::Input::Pause).unwrap();
+    machine.consume(&player::Input::Interrupt).unwrap();
+    machine.history_mut().clear();
+    machine.consume(&player::Input::Resume).unwrap();
+    assert_eq!(machine.state(), &active(player::ActiveState::Playing));
+}
+
+#[test]
+fn history_is_stored_in_wrapper() {
+    let mut machine = player::StateMachine::new();
+    machine.consume(&player::Input::Play).unwrap();
+    machine.consume(&player::Input::Pause).unwrap();
+    machine.consume(&player::Input::Stop).unwrap();
+    assert_eq!(
+        machine.history().get().Active,
+        Some(player::ActiveState::Paused)
+    );
+
+    // The transition function enters the initial substate
+    assert_eq!(
+        <player::Impl as StateMachineImpl>::transition(
+            &player::State::Interrupted,
+            &player::Input::Resume
+        ),
+        Some(active(player::ActiveState::Playing))
+    );
+}
+
+#[test]
+fn replay_rebuilds_history() {
+    let inputs = [
+        player::Input::Play,
+        player::Input::Pause,
+        player::Input::Interrupt,
+        player::Input::Resume,
+    ];
+    let machine = player::StateMachine::replay(&inputs).unwrap();
+    assert_eq!(machine.state(), &active(player::ActiveState::Paused));
+}
@@ -0,0 +1,174 @@
+/// Test for entry, exit and transition hooks
+use rust_fsm::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[derive(Debug, Default)]
+pub struct Timers {
+    log: Vec<String>,
+}
+
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    #[state_machine(context(crate::Timers))]
+    circuit_breaker(Closed)
+
+    use super::Timers;
+
+    Closed(Unsuccessful) => Open,
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open
+    },
+    Open => on_enter [|timers: &mut Timers| timers.log.push("start timer".to_string())],
+    Open => on_exit [|timers: &mut Timers| timers.log.push("stop timer".to_string())],
+    Open => on_exit [|timers: &mut Timers| timers.log.push("open left".to_string())],
+    HalfOpen => on_enter [|timers: &mut Timers| timers.log.push("probe".to_string())]
+}
+
+#[test]
+fn hooks_with_context() {
+    let mut timers = Timers::default();
+    let mut machine = circuit_breaker::StateMachine::new();
+
+    machine
+        .consume_with(&mut timers, &circuit_breaker::Input::Unsuccessful)
+        .unwrap();
+    assert_eq!(timers.log, ["start timer"]);
+
+    machine
+        .consume_with(&mut timers, &circuit_breaker::Input::TimerTriggered)
+        .unwrap();
+    assert_eq!(
+        timers.log,
+        ["start timer", "stop timer", "open left", "probe"]
+    );
+
+    // Hooks are not called when the transition is impossible
+    assert!(machine
+        .consume_with(&mut timers, &circuit_breaker::Input::TimerTriggered)
+        .is_err());
+    assert_eq!(timers.log.len(), 4);
+}
+
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    #[state_machine(context(crate::Timers))]
+    turnstile(Locked)
+
+    use super::Timers;
+
+    Locked(Coin) => Unlocked,
+    Unlocked => {
+        Coin => Unlocked,
+        Push => Locked
+    },
+    Unlocked => on_exit [|timers: &mut Timers| timers.log.push("exit".to_string())],
+    Unlocked => on_transition [|timers: &mut Timers, input: &Input, to: &State| {
+        timers.log.push(format!("{input:?} => {to:?}"));
+    }],
+    Locked => on_enter [|timers: &mut Timers| timers.log.push("enter".to_string())]
+}
+
+#[test]
+fn transition_hooks() {
+    let mut timers = Timers::default();
+    let mut machine = turnstile::StateMachine::new();
+
+    machine
+        .consume_with(&mut timers, &turnstile::Input::Coin)
+        .unwrap();
+    assert!(timers.log.is_empty());
+
+    machine
+        .consume_with(&mut timers, &turnstile::Input::Coin)
+        .unwrap();
+    machine
+        .consume_with(&mut timers, &turnstile::Input::Push)
+        .unwrap();
+    assert_eq!(
+        timers.log,
+        [
+            "exit",
+            "Coin => Unlocked",
+            "exit",
+            "Push => Locked",
+            "enter"
+        ]
+    );
+}
+
+static ENTERED_FAILED: AtomicU32 = AtomicU32::new(0);
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    job(Running)
+
+    use super::ENTERED_FAILED;
+    use std::sync::atomic::Ordering;
+
+    Running(Fail) => Failed(1),
+    Failed(u32) => {
+        Fail => Failed(|attempts: &u32| attempts + 1),
+        Retry => Running
+    },
+    Failed(u32) => on_enter [|attempts: &u32| {
+        ENTERED_FAILED.store(*attempts, Ordering::SeqCst);
+    }]
+}
+
+#[test]
+fn hooks_with_state_data() {
+    let mut machine = job::StateMachine::new();
+
+    machine.consume(&job::Input::Fail).unwrap();
+    assert_eq!(ENTERED_FAILED.load(Ordering::SeqCst), 1);
+
+    // Self-transitions exit and enter the state again
+    machine.consume(&job::Input::Fail).unwrap();
+    assert_eq!(ENTERED_FAILED.load(Ordering::SeqCst), 2);
+
+    machine.consume(&job::Input::Retry).unwrap();
+    assert_eq!(machine.state(), &job::State::Running);
+}
+
+static TRANSITIONS: AtomicU32 = AtomicU32::new(0);
+static EXITS: AtomicU32 = AtomicU32::new(0);
+
+struct Toggle;
+
+impl StateMachineImpl for Toggle {
+    type Input = ();
+    type State = bool;
+    type Output = ();
+    const INITIAL_STATE: Self::State = false;
+
+    fn transition(state: &bool, _: &()) -> Option<bool> {
+        Some(!state)
+    }
+
+    fn output(_: &bool, _: &()) -> Option<()> {
+        None
+    }
+
+    fn on_exit(state: &bool) {
+        assert_eq!(*state, TRANSITIONS.load(Ordering::SeqCst) % 2 == 1);
+        EXITS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_transition(from: &bool, _: &(), to: &bool) {
+        assert_ne!(from, to);
+        TRANSITIONS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn hooks_without_dsl() {
+    let mut machine: StateMachine<Toggle> = StateMachine::new();
+    machine.consume(&()).unwrap();
+    machine.consume(&()).unwrap();
+    machine.consume(&()).unwrap();
+    assert_eq!(TRANSITIONS.load(Ordering::SeqCst), 3);
+    assert_eq!(EXITS.load(Ordering::SeqCst), 3);
+    assert!(*machine.state());
+}
@@ -0,0 +1,107 @@
+/// Test for the reachability lints of the state machine definitions
+use rust_fsm::*;
+
+// The lints are denied, so the state machines only compile if they have no
+// unreachable and dead-end states
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(final(Shipped, Cancelled))]
+    #[state_machine(deny(unreachable_states, dead_end_states))]
+    order(Placed)
+
+    Placed => {
+        Pay => Paid,
+        Cancel => Cancelled,
+    },
+    Paid(Ship) => Shipped,
+}
+
+// The substates inherit the transitions of the composite states, and the final
+// composite states make all their substates final
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(final(Archived))]
+    #[state_machine(deny(unreachable_states, dead_end_states))]
+    document(Draft)
+
+    Draft(Publish) => Published,
+    Published(Visible) {
+        Visible(Hide) => Hidden,
+        Hidden(Show) => Visible,
+    },
+    Published(Archive) => Archived,
+    Archived(Stored) {
+        Stored(Restore) => Restored,
+        Restored,
+    },
+}
+
+// Timeouts are transitions too
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(deny(unreachable_states, dead_end_states))]
+    light(Off)
+
+    Off(Press) => On,
+    On => after(10s) => Off,
+}
+
+// The state machines declaring no final states may end in any state
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(deny(unreachable_states))]
+    door(Open)
+
+    Open(Key) => Closed,
+    Closed(Key) => Open,
+    Open(Break) => Broken,
+    Closed(Break) => Broken,
+}
+
+// The lints may be disabled
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(allow(unreachable_states, dead_end_states))]
+    legacy(Active)
+
+    Active(Retire) => Retired,
+    Dormant(Wake) => Active,
+}
+
+#[test]
+fn final_states() {
+    let mut machine = order::StateMachine::new();
+    machine.consume(&order::Input::Pay).unwrap();
+    machine.consume(&order::Input::Ship).unwrap();
+    assert_eq!(machine.state(), &order::State::Shipped);
+}
+
+#[test]
+fn inherited_transitions() {
+    let mut machine = document::StateMachine::new();
+    machine.consume(&document::Input::Publish).unwrap();
+    machine.consume(&document::Input::Hide).unwrap();
+    machine.consume(&document::Input::Archive).unwrap();
+    machine.consume(&document::Input::Restore).unwrap();
+    assert_eq!(
+        machine.state(),
+        &document::State::Archived(document::ArchivedState::Restored)
+    );
+}
+
+#[test]
+fn undeclared_final_states() {
+    let mut machine = door::StateMachine::new();
+    machine.consume(&door::Input::Break).unwrap();
+    assert_eq!(machine.state(), &door::State::Broken);
+    assert!(machine.consume(&door::Input::Key).is_err());
+}
+
+#[test]
+fn allowed_lints() {
+    let mut machine = legacy::StateMachine::from_state(legacy::State::Dormant);
+    machine.consume(&legacy::Input::Wake).unwrap();
+    machine.consume(&legacy::Input::Retire).unwrap();
+    assert_eq!(machine.state(), &legacy::State::Retired);
+}
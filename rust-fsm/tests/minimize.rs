@@ -0,0 +1,140 @@
+#![cfg(feature = "std")]
+/// Test for finding the equivalent states of state machines
+use rust_fsm::*;
+
+// The equivalent states are reported with a `deprecated` warning
+#[allow(deprecated)]
+mod machines {
+    use rust_fsm::*;
+
+    state_machine! {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[state_machine(minimize)]
+        pub door(Closed)
+
+        Closed(Push) => Open,
+        Open => {
+            Close => Closed [Slammed],
+            Push => Wide,
+        },
+        Wide => {
+            Close => Closed [Slammed],
+            Push => Wide,
+        },
+    }
+
+    // The same transitions declared in a different order
+    state_machine! {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[state_machine(minimize)]
+        pub gate(Closed)
+
+        Closed(Push) => Open,
+        Open => {
+            Close => Closed [Slammed],
+            Push => Wide,
+        },
+        Wide => {
+            Push => Wide,
+            Close => Closed [Slammed],
+        },
+    }
+
+    state_machine! {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[state_machine(minimize)]
+        pub relay(Off)
+
+        Off(Toggle) => On,
+        On(Toggle) => Off,
+        On(Reset) => Off,
+        Idle(Toggle) => Standby,
+        Standby(Toggle) => Idle,
+        Standby(Reset) => Idle,
+        Stuck(Toggle) => Jammed,
+        Jammed(Toggle) => Stuck,
+        Jammed(Reset) => Stuck [Alarm],
+    }
+
+    state_machine! {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[state_machine(minimize)]
+        pub turnstile(Locked)
+
+        Locked(Coin) => Unlocked [Unlock],
+        Unlocked(Push) => Locked [Lock],
+    }
+}
+
+use machines::*;
+
+#[test]
+fn dsl_reports_equivalent_states() {
+    assert_eq!(door::EQUIVALENT_STATES, &[&["Open", "Wide"]]);
+    assert_eq!(gate::EQUIVALENT_STATES, &[&["Open", "Wide"]]);
+    // The states reach each other in cycles
+    assert_eq!(
+        relay::EQUIVALENT_STATES,
+        &[&["Idle", "Off"], &["On", "Standby"]]
+    );
+    assert!(turnstile::EQUIVALENT_STATES.is_empty());
+}
+
+#[test]
+fn outputs_distinguish_states() {
+    // `Jammed` outputs `Alarm` on `Reset`, so neither it nor `Stuck` is
+    // equivalent to any other state
+    assert_eq!(
+        equivalent_states::<relay::Impl>(),
+        vec![
+            vec![relay::State::Idle, relay::State::Off],
+            vec![relay::State::On, relay::State::Standby],
+        ]
+    );
+}
+
+#[test]
+fn runtime_analysis() {
+    assert_eq!(
+        <door::Impl as IntrospectImpl>::STATES,
+        &[door::State::Closed, door::State::Open, door::State::Wide]
+    );
+    assert_eq!(
+        equivalent_states::<door::Impl>(),
+        vec![vec![door::State::Open, door::State::Wide]]
+    );
+    assert_eq!(
+        equivalent_states::<gate::Impl>(),
+        vec![vec![gate::State::Open, gate::State::Wide]]
+    );
+    assert!(equivalent_states::<turnstile::Impl>().is_empty());
+}
+
+/// Counts the inputs modulo 4 but only outputs the parity, so the states
+/// differing by 2 are equivalent
+pub struct Counter;
+
+impl StateMachineImpl for Counter {
+    type Input = ();
+    type State = u8;
+    type Output = bool;
+    const INITIAL_STATE: Self::State = 0;
+
+    fn transition(state: &Self::State, _: &Self::Input) -> Option<Self::State> {
+        Some((state + 1) % 4)
+    }
+
+    fn output(state: &Self::State, _: &Self::Input) -> Option<Self::Output> {
+        Some((state + 1) % 2 == 0)
+    }
+}
+
+impl IntrospectImpl for Counter {
+    const STATES: &'static [Self::State] = &[0, 1, 2, 3];
+    const INPUTS: &'static [Self::Input] = &[()];
+}
+
+#[test]
+fn manual_implementation() {
+    assert_eq!(equivalent_states::<Counter>(), vec![vec![0, 2], vec![1, 3]]);
+}
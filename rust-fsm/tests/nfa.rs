@@ -0,0 +1,144 @@
+#![cfg(feature = "std")]
+/// Test for nondeterministic automata and their conversion to deterministic
+/// state machines
+use rust_fsm::*;
+use std::{collections::BTreeSet, sync::OnceLock};
+
+/// Accepts the strings ending with "ab"
+struct EndsWithAb;
+
+impl NfaImpl for EndsWithAb {
+    type Input = char;
+    type State = u8;
+    const INITIAL_STATE: u8 = 0;
+
+    fn transition(state: &u8, input: &char) -> impl IntoIterator<Item = u8> {
+        match (state, input) {
+            (0, 'a') => vec![0, 1],
+            (0, 'b') => vec![0],
+            (1, 'b') => vec![2],
+            _ => vec![],
+        }
+    }
+
+    fn is_accepting(state: &u8) -> bool {
+        *state == 2
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Star {
+    A,
+    B,
+}
+
+/// Accepts `a*b*`, moving from the loop over `a` to the loop over `b` with an
+/// epsilon move
+struct AsThenBs;
+
+impl NfaImpl for AsThenBs {
+    type Input = char;
+    type State = Star;
+    const INITIAL_STATE: Star = Star::A;
+
+    fn transition(state: &Star, input: &char) -> impl IntoIterator<Item = Star> {
+        match (state, input) {
+            (Star::A, 'a') => Some(Star::A),
+            (Star::B, 'b') => Some(Star::B),
+            _ => None,
+        }
+    }
+
+    fn epsilon(state: &Star) -> impl IntoIterator<Item = Star> {
+        (*state == Star::A).then_some(Star::B)
+    }
+
+    fn is_accepting(_: &Star) -> bool {
+        true
+    }
+}
+
+struct EndsWithAbDfa;
+
+impl DfaImpl for EndsWithAbDfa {
+    type Nfa = EndsWithAb;
+
+    fn table() -> &'static DfaTable<EndsWithAb> {
+        static TABLE: OnceLock<DfaTable<EndsWithAb>> = OnceLock::new();
+        TABLE.get_or_init(|| DfaTable::build(['a', 'b']))
+    }
+}
+
+fn accepts<N: NfaImpl<Input = char>>(input: &str) -> bool {
+    let mut machine = NfaStateMachine::<N>::new();
+    input.chars().all(|c| machine.consume(&c).is_ok()) && machine.is_accepting()
+}
+
+#[test]
+fn nfa_tracks_active_states() {
+    let mut machine = NfaStateMachine::<EndsWithAb>::new();
+    assert_eq!(machine.states(), &BTreeSet::from([0]));
+    machine.consume(&'a').unwrap();
+    assert_eq!(machine.states(), &BTreeSet::from([0, 1]));
+    assert!(!machine.is_accepting());
+    machine.consume(&'b').unwrap();
+    assert_eq!(machine.states(), &BTreeSet::from([0, 2]));
+    assert!(machine.is_accepting());
+
+    // No active state has a transition, so the states are kept
+    assert!(machine.consume(&'c').is_err());
+    assert_eq!(machine.states(), &BTreeSet::from([0, 2]));
+}
+
+#[test]
+fn nfa_follows_epsilon_moves() {
+    let machine = NfaStateMachine::<AsThenBs>::new();
+    assert_eq!(machine.states(), &BTreeSet::from([Star::A, Star::B]));
+
+    assert!(accepts::<AsThenBs>(""));
+    assert!(accepts::<AsThenBs>("aab"));
+    assert!(accepts::<AsThenBs>("bb"));
+    assert!(!accepts::<AsThenBs>("aba"));
+}
+
+#[test]
+fn table_is_built_by_subset_construction() {
+    let table = DfaTable::<EndsWithAb>::build(['a', 'b']);
+    assert_eq!(table.len(), 3);
+    assert_eq!(table.states(0), &BTreeSet::from([0]));
+
+    let a = table.next(0, &'a').unwrap();
+    assert_eq!(table.states(a), &BTreeSet::from([0, 1]));
+    let ab = table.next(a, &'b').unwrap();
+    assert_eq!(table.states(ab), &BTreeSet::from([0, 2]));
+    assert!(table.is_accepting(ab));
+    assert_eq!(table.next(ab, &'b'), Some(0));
+    assert_eq!(table.next(ab, &'c'), None);
+
+    let table = DfaTable::<AsThenBs>::build(['a', 'b']);
+    let b = table.next(0, &'b').unwrap();
+    assert_eq!(table.states(b), &BTreeSet::from([Star::B]));
+    assert_eq!(table.next(b, &'a'), None);
+}
+
+#[test]
+fn dfa_matches_nfa() {
+    for input in ["", "a", "ab", "aab", "abab", "abb", "ba", "bab"] {
+        let mut machine = StateMachine::<Dfa<EndsWithAbDfa>>::new();
+        let mut output = None;
+        for c in input.chars() {
+            output = machine.consume(&c).unwrap();
+        }
+        assert_eq!(output.is_some(), accepts::<EndsWithAb>(input), "{input}");
+        assert_eq!(
+            EndsWithAbDfa::table().is_accepting(*machine.state()),
+            accepts::<EndsWithAb>(input),
+            "{input}"
+        );
+    }
+
+    let mut machine = StateMachine::<Dfa<EndsWithAbDfa>>::new();
+    assert_eq!(machine.consume(&'a'), Ok(None));
+    assert_eq!(machine.consume(&'b'), Ok(Some(Accepted)));
+    assert!(machine.consume(&'c').is_err());
+}
@@ -0,0 +1,151 @@
+/// Test for running two state machines in lockstep as their product
+use rust_fsm::*;
+use std::cell::Cell;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Message {
+    Hello,
+    Order,
+    Pay,
+    Bye,
+}
+
+thread_local! {
+    // The tests run in their own threads
+    static ORDERS: Cell<u32> = const { Cell::new(0) };
+    static GUARDS: Cell<u32> = const { Cell::new(0) };
+}
+
+pub fn counted() -> bool {
+    GUARDS.with(|guards| guards.set(guards.get() + 1));
+    true
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(input(crate::Message))]
+    protocol(Idle)
+
+    Idle(Hello) => Session,
+    Session => {
+        Order => Session,
+        Pay => Session,
+        Bye => Idle [Closed],
+    },
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(input(crate::Message))]
+    workflow(Empty)
+
+    use super::{counted, ORDERS};
+
+    Empty(Order) => Ordered [Reserved],
+    Ordered => {
+        Order => Ordered [Reserved],
+        Pay if counted() => Paid [Charged],
+    },
+    Ordered => on_enter [|| {
+        ORDERS.with(|orders| orders.set(orders.get() + 1));
+    }],
+}
+
+type Checked = Product<protocol::Impl, workflow::Impl>;
+type Either = Product<protocol::Impl, workflow::Impl, rust_fsm::Either>;
+
+#[test]
+fn both_must_accept() {
+    let mut machine = StateMachine::<Checked>::new();
+    assert_eq!(
+        machine.state(),
+        &(protocol::State::Idle, workflow::State::Empty)
+    );
+
+    // The workflow rejects the greeting
+    let err = machine.try_consume(&Message::Hello).unwrap_err();
+    assert_eq!(err.reason, RejectionReason::NoTransition);
+    assert_eq!(
+        machine.state(),
+        &(protocol::State::Idle, workflow::State::Empty)
+    );
+
+    // The protocol rejects the order outside of a session
+    assert!(machine.consume(&Message::Order).is_err());
+
+    let mut machine =
+        StateMachine::<Checked>::from_state((protocol::State::Session, workflow::State::Empty));
+    assert_eq!(
+        machine.consume(&Message::Order),
+        Ok(Some((None, Some(workflow::Output::Reserved))))
+    );
+    assert_eq!(
+        machine.consume(&Message::Pay),
+        Ok(Some((None, Some(workflow::Output::Charged))))
+    );
+    assert_eq!(
+        machine.state(),
+        &(protocol::State::Session, workflow::State::Paid)
+    );
+}
+
+#[test]
+fn either_may_accept() {
+    let mut machine = StateMachine::<Either>::new();
+    assert_eq!(machine.consume(&Message::Hello), Ok(None));
+    assert_eq!(
+        machine.state(),
+        &(protocol::State::Session, workflow::State::Empty)
+    );
+    machine.consume(&Message::Order).unwrap();
+    assert_eq!(
+        machine.consume(&Message::Bye),
+        Ok(Some((Some(protocol::Output::Closed), None)))
+    );
+    assert_eq!(
+        machine.state(),
+        &(protocol::State::Idle, workflow::State::Ordered)
+    );
+
+    // Neither accepts it
+    assert!(machine.consume(&Message::Bye).is_err());
+}
+
+#[test]
+fn hooks_follow_the_transitions() {
+    let mut machine = StateMachine::<Either>::new();
+    machine.consume(&Message::Hello).unwrap();
+    assert_eq!(ORDERS.get(), 0);
+    machine.consume(&Message::Order).unwrap();
+    machine.consume(&Message::Order).unwrap();
+    assert_eq!(ORDERS.get(), 2);
+}
+
+#[test]
+fn guards_run_once() {
+    let mut machine =
+        StateMachine::<Checked>::from_state((protocol::State::Session, workflow::State::Ordered));
+    machine.consume(&Message::Pay).unwrap();
+    assert_eq!(GUARDS.get(), 1);
+
+    let mut machine =
+        StateMachine::<Either>::from_state((protocol::State::Idle, workflow::State::Ordered));
+    machine.consume(&Message::Pay).unwrap();
+    assert_eq!(GUARDS.get(), 2);
+}
+
+#[test]
+fn rejection_reason_of_rejecting_machine() {
+    // The workflow would accept the payment, but the protocol has no
+    // transition for it
+    let mut machine =
+        StateMachine::<Checked>::from_state((protocol::State::Idle, workflow::State::Ordered));
+    let err = machine.try_consume(&Message::Pay).unwrap_err();
+    assert_eq!(err.reason, RejectionReason::NoTransition);
+
+    // The workflow rejects the payment before any order
+    let mut machine =
+        StateMachine::<Checked>::from_state((protocol::State::Session, workflow::State::Empty));
+    let err = machine.try_consume(&Message::Pay).unwrap_err();
+    assert_eq!(err.reason, RejectionReason::NoTransition);
+}
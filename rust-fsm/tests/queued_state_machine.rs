@@ -0,0 +1,90 @@
+#![cfg(feature = "std")]
+/// Test for the run-to-completion event queue with internally raised events
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    order(Placed)
+
+    Placed(Pay) => Paid [ReserveStock],
+    Paid => {
+        StockReserved => Reserved [Ship],
+        Cancel => Cancelled
+    },
+    Reserved => {
+        Shipped => Completed,
+        Cancel => Cancelled
+    }
+}
+
+#[test]
+fn internal_events_take_priority() {
+    let mut queue = QueuedStateMachine::from(order::StateMachine::new());
+    queue.push(order::Input::Pay);
+    // Processed after the internal events raised by `Pay`
+    queue.push(order::Input::Cancel);
+
+    let mut outputs = Vec::new();
+    let res = queue.run(|output, raise| {
+        match output {
+            order::Output::ReserveStock => raise.raise(order::Input::StockReserved),
+            order::Output::Ship => raise.raise(order::Input::Shipped),
+        }
+        outputs.push(output);
+    });
+
+    // `Cancel` is rejected because the order is already completed
+    assert_eq!(
+        res,
+        Err(RunError::Rejected {
+            input: order::Input::Cancel,
+            reason: RejectionReason::NoTransition,
+        })
+    );
+    assert_eq!(outputs, [order::Output::ReserveStock, order::Output::Ship]);
+    assert_eq!(queue.state(), &order::State::Completed);
+    assert_eq!(queue.pending(), 0);
+}
+
+#[test]
+fn run_returns_consumed_steps() {
+    let mut queue = QueuedStateMachine::<order::Impl>::new();
+    queue.push(order::Input::Pay);
+    queue.push(order::Input::StockReserved);
+    queue.push(order::Input::Cancel);
+
+    assert_eq!(queue.run(|_, _| {}), Ok(3));
+    assert_eq!(queue.state(), &order::State::Cancelled);
+    assert_eq!(queue.run(|_, _| {}), Ok(0));
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    ping_pong(Ping)
+
+    Ping(Hit) => Pong [Serve],
+    Pong(Hit) => Ping [Serve]
+}
+
+#[test]
+fn step_budget_stops_infinite_loops() {
+    let mut queue = QueuedStateMachine::<ping_pong::Impl>::new().with_step_budget(10);
+    assert_eq!(queue.step_budget(), 10);
+    queue.push(ping_pong::Input::Hit);
+
+    let mut served = 0;
+    let mut serve = |_, raise: &mut Raise<'_, ping_pong::Input>| {
+        served += 1;
+        raise.raise(ping_pong::Input::Hit);
+    };
+
+    assert_eq!(queue.run(&mut serve), Err(RunError::StepBudgetExceeded));
+    // The pending event is kept, so the run can be resumed
+    assert_eq!(queue.pending(), 1);
+    assert_eq!(queue.run(&mut serve), Err(RunError::StepBudgetExceeded));
+    assert_eq!(served, 20);
+    assert_eq!(
+        RunError::<ping_pong::Input>::StepBudgetExceeded.to_string(),
+        "the step budget is exceeded"
+    );
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "std")]
+/// Test for recording the transition history of a state machine
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    order(Placed)
+
+    Placed(Pay(u32)) => Paid [Charged],
+    Paid => {
+        Ship => Shipped,
+        Fail => Failed [Refunded]
+    },
+    Failed(Retry) => Placed
+}
+
+#[test]
+fn vec_recorder_keeps_all_transitions() {
+    let mut machine = order::StateMachine::new().with_recorder(VecRecorder::new());
+    machine.consume(&order::Input::Pay(10)).unwrap();
+    machine.consume(&order::Input::Fail).unwrap();
+    machine.consume(&order::Input::Retry).unwrap();
+    machine.consume(&order::Input::Pay(20)).unwrap();
+    machine.consume(&order::Input::Fail).unwrap();
+    // Rejected inputs are not recorded
+    assert!(machine.consume(&order::Input::Ship).is_err());
+
+    let recorder = machine.recorder();
+    assert_eq!(recorder.len(), 5);
+    assert_eq!(
+        recorder.records()[0],
+        TransitionRecord {
+            from: order::State::Placed,
+            input: order::Input::Pay(10),
+            to: order::State::Paid,
+            output: Some(order::Output::Charged),
+        }
+    );
+
+    // How did the order end up in `Failed`?
+    let failures = recorder
+        .iter()
+        .filter(|record| record.to == order::State::Failed)
+        .map(|record| &record.from)
+        .collect::<Vec<_>>();
+    assert_eq!(failures, [&order::State::Paid, &order::State::Paid]);
+    let last_payment = recorder.iter().rev().find_map(|record| match record.input {
+        order::Input::Pay(amount) => Some(amount),
+        _ => None,
+    });
+    assert_eq!(last_payment, Some(20));
+
+    machine.recorder_mut().clear();
+    assert!(machine.recorder().is_empty());
+    assert_eq!(machine.state(), &order::State::Failed);
+}
+
+#[test]
+fn ring_buffer_recorder_keeps_last_transitions() {
+    let mut machine: StateMachine<order::Impl, RingBufferRecorder<order::Impl, 2>> =
+        StateMachine::new();
+    assert!(machine.recorder().is_empty());
+
+    machine.consume(&order::Input::Pay(10)).unwrap();
+    assert_eq!(machine.recorder().len(), 1);
+    machine.consume(&order::Input::Fail).unwrap();
+    machine.consume(&order::Input::Retry).unwrap();
+
+    let inputs = machine
+        .recorder()
+        .iter()
+        .map(|record| record.input.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(inputs, [order::Input::Fail, order::Input::Retry]);
+
+    machine.recorder_mut().clear();
+    assert_eq!(machine.recorder().iter().count(), 0);
+    machine.consume(&order::Input::Pay(30)).unwrap();
+    assert_eq!(
+        format!("{:?}", machine.recorder()),
+        "[TransitionRecord { from: Placed, input: Pay(30), to: Paid, output: Some(Charged) }]"
+    );
+}
@@ -0,0 +1,138 @@
+/// Test for orthogonal regions receiving the same inputs
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    device(power: Awake, link: Offline)
+
+    Awake => {
+        Sleep => Asleep [Dimmed],
+        Shutdown => Off,
+    },
+    Asleep(Wake) => Awake,
+    --
+    Offline(Connect) => Online [Connected],
+    Online => {
+        Disconnect => Offline,
+        Sleep => Offline [Disconnected],
+        Send(u32) if |size: &u32| *size <= 1024 => Online,
+    },
+}
+
+#[test]
+fn regions_start_in_their_initial_states() {
+    let machine = device::StateMachine::new();
+    assert_eq!(
+        machine.state(),
+        &(device::power::State::Awake, device::link::State::Offline)
+    );
+}
+
+#[test]
+fn input_consumed_by_one_region() {
+    let mut machine = device::StateMachine::new();
+    assert_eq!(
+        machine.consume(&device::Input::Connect),
+        Ok(Some([None, Some(device::Output::Connected)]))
+    );
+    assert_eq!(
+        machine.state(),
+        &(device::power::State::Awake, device::link::State::Online)
+    );
+}
+
+#[test]
+fn input_consumed_by_all_regions() {
+    let mut machine = device::StateMachine::from_state((
+        device::power::State::Awake,
+        device::link::State::Online,
+    ));
+    assert_eq!(
+        machine.consume(&device::Input::Sleep),
+        Ok(Some([
+            Some(device::Output::Dimmed),
+            Some(device::Output::Disconnected)
+        ]))
+    );
+    assert_eq!(
+        machine.state(),
+        &(device::power::State::Asleep, device::link::State::Offline)
+    );
+
+    // No region outputs anything
+    machine.consume(&device::Input::Wake).unwrap();
+    assert_eq!(machine.consume(&device::Input::Shutdown), Ok(None));
+    assert_eq!(
+        machine.state(),
+        &(device::power::State::Off, device::link::State::Offline)
+    );
+}
+
+#[test]
+fn input_rejected_by_all_regions() {
+    let mut machine = device::StateMachine::new();
+    let err = machine.try_consume(&device::Input::Wake).unwrap_err();
+    assert_eq!(err.reason, RejectionReason::NoTransition);
+
+    machine.consume(&device::Input::Connect).unwrap();
+    let err = machine.try_consume(&device::Input::Send(4096)).unwrap_err();
+    let RejectionReason::GuardsRejected(guards) = err.reason else {
+        panic!("Expected the guards to reject the input");
+    };
+    assert_eq!(guards.len(), 1);
+}
+
+#[test]
+fn regions_are_state_machines() {
+    let mut power = device::power::StateMachine::new();
+    assert_eq!(
+        power.consume(&device::Input::Sleep),
+        Ok(Some(device::Output::Dimmed))
+    );
+    assert!(power.consume(&device::Input::Connect).is_err());
+}
+
+#[derive(Default)]
+pub struct Counters {
+    pub sent: u32,
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(context(crate::Counters))]
+    counted(link: Offline, traffic: Idle)
+
+    use super::Counters;
+
+    Offline(Connect) => Online,
+    Online(Disconnect) => Offline,
+    --
+    Idle => {
+        Send(u32) => Idle [|counters: &mut Counters, size: &u32| {
+            counters.sent += size;
+            Output::Sent
+        }],
+        Flush => Idle [Sent],
+    },
+}
+
+#[test]
+fn regions_share_the_context() {
+    let mut machine = counted::StateMachine::new();
+    let mut counters = Counters::default();
+    assert_eq!(
+        machine.consume_with(&mut counters, &counted::Input::Send(3)),
+        Ok(Some([None, Some(counted::Output::Sent)]))
+    );
+    machine
+        .consume_with(&mut counters, &counted::Input::Connect)
+        .unwrap();
+    machine
+        .consume_with(&mut counters, &counted::Input::Send(4))
+        .unwrap();
+    assert_eq!(counters.sent, 7);
+    assert_eq!(
+        machine.state(),
+        &(counted::link::State::Online, counted::traffic::State::Idle)
+    );
+}
@@ -0,0 +1,77 @@
+/// Test for errors carrying the rejected state and input
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    payment(Idle)
+
+    Idle(Start(u32)) => Processing,
+    Processing => {
+        Complete => Done [Paid],
+        Fail(String) => Failed(|reason: &String| reason.clone())
+    },
+    Failed(String) => {
+        Retry => Processing
+    }
+}
+
+#[test]
+fn rejected_transition_carries_state_and_input() {
+    let mut machine = payment::StateMachine::new();
+
+    let err = machine.try_consume(&payment::Input::Complete).unwrap_err();
+    assert_eq!(err.state, &payment::State::Idle);
+    assert_eq!(err.input, &payment::Input::Complete);
+    assert_eq!(
+        err.to_string(),
+        "cannot perform a state transition from the state Idle with the input Complete: \
+         no transition is defined"
+    );
+
+    machine.try_consume(&payment::Input::Start(10)).unwrap();
+    machine
+        .try_consume(&payment::Input::Fail("timeout".to_string()))
+        .unwrap();
+
+    let err = machine
+        .try_consume(&payment::Input::Start(20))
+        .unwrap_err()
+        .cloned();
+    assert_eq!(
+        err,
+        TransitionRejectedError {
+            state: payment::State::Failed("timeout".to_string()),
+            input: payment::Input::Start(20),
+            reason: RejectionReason::NoTransition,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "cannot perform a state transition from the state Failed(\"timeout\") with the input \
+         Start(20): no transition is defined"
+    );
+
+    // The state machine is still usable after the owned error is produced
+    machine.try_consume(&payment::Input::Retry).unwrap();
+    assert_eq!(machine.state(), &payment::State::Processing);
+}
+
+#[test]
+fn rejected_transition_converts_into_impossible_error() {
+    fn consume(
+        machine: &mut payment::StateMachine,
+        input: &payment::Input,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        machine.try_consume(input).map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    let mut machine = payment::StateMachine::new();
+    assert!(consume(&mut machine, &payment::Input::Retry).is_err());
+
+    let err: TransitionImpossibleError = machine
+        .try_consume(&payment::Input::Retry)
+        .unwrap_err()
+        .into();
+    assert_eq!(err, TransitionImpossibleError);
+}
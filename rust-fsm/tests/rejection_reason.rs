@@ -0,0 +1,90 @@
+/// Test for telling missing transitions from transitions rejected by guards
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    payment_system(Idle)
+
+    Idle => {
+        StartPayment(u32) if |amount: &u32| *amount >= 100 => Processing,
+        StartPayment(u32) if |amount: &u32| *amount >= 10 => Idle [InsufficientAmount],
+        Cancel => Idle
+    },
+    Processing => {
+        Complete => Success,
+        Fail => Failed
+    },
+    Failed => {
+        Retry(u32) if |attempts: &u32| *attempts < 3 => Processing,
+        Retry(u32) => Failed [MaxRetriesExceeded],
+        Cancel => Idle
+    },
+    Success(Reset) => Idle
+}
+
+#[test]
+fn guards_rejected() {
+    let mut machine = payment_system::StateMachine::new();
+
+    let err = machine
+        .try_consume(&payment_system::Input::StartPayment(5))
+        .unwrap_err();
+    let RejectionReason::GuardsRejected(guards) = err.reason else {
+        panic!("Expected the guards to reject the input");
+    };
+    assert_eq!(guards.len(), 2);
+    assert!(guards[0].contains("100"));
+    assert!(guards[1].contains("10"));
+    assert!(err
+        .to_string()
+        .starts_with("cannot perform a state transition from the state Idle with the input StartPayment(5): rejected by the guards `"));
+}
+
+#[test]
+fn no_transition() {
+    let mut machine = payment_system::StateMachine::new();
+
+    let err = machine
+        .try_consume(&payment_system::Input::Complete)
+        .unwrap_err();
+    assert_eq!(err.reason, RejectionReason::NoTransition);
+
+    // A guarded transition for another state is irrelevant
+    machine
+        .consume(&payment_system::Input::StartPayment(100))
+        .unwrap();
+    let err = machine
+        .try_consume(&payment_system::Input::StartPayment(100))
+        .unwrap_err();
+    assert_eq!(err.reason, RejectionReason::NoTransition);
+}
+
+#[derive(Debug)]
+struct Manual;
+
+impl StateMachineImpl for Manual {
+    type Input = u32;
+    type State = ();
+    type Output = ();
+    const INITIAL_STATE: Self::State = ();
+
+    fn transition(_: &(), input: &u32) -> Option<()> {
+        (*input > 0).then_some(())
+    }
+
+    fn output(_: &(), _: &u32) -> Option<()> {
+        None
+    }
+
+    fn rejection_reason(_: &(), _: &u32) -> RejectionReason {
+        RejectionReason::GuardsRejected(&["input > 0"])
+    }
+}
+
+#[test]
+fn rejection_reason_without_dsl() {
+    let mut machine: StateMachine<Manual> = StateMachine::new();
+    machine.consume(&1).unwrap();
+    let err = machine.try_consume(&0).unwrap_err();
+    assert_eq!(err.reason, RejectionReason::GuardsRejected(&["input > 0"]));
+}
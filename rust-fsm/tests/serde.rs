@@ -0,0 +1,109 @@
+#![cfg(feature = "serde")]
+/// Test for persisting state machines with serde
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(serde(state, input))]
+    workflow(Draft)
+
+    Draft(Submit) => Review,
+    Review => {
+        Approve(u32) => Approved(|reviewer: &u32| *reviewer),
+        Reject => Draft [Rejected]
+    },
+    Approved(u32) => {}
+}
+
+#[test]
+fn serialize_generated_enums() {
+    let state = workflow::State::Approved(7);
+    let json = serde_json::to_string(&state).unwrap();
+    assert_eq!(json, r#"{"Approved":7}"#);
+    assert_eq!(
+        serde_json::from_str::<workflow::State>(&json).unwrap(),
+        state
+    );
+
+    let input = workflow::Input::Approve(7);
+    let json = serde_json::to_string(&input).unwrap();
+    assert_eq!(
+        serde_json::from_str::<workflow::Input>(&json).unwrap(),
+        input
+    );
+}
+
+#[test]
+fn persist_state_machine() {
+    let mut machine = workflow::StateMachine::new();
+    machine.consume(&workflow::Input::Submit).unwrap();
+
+    let json = serde_json::to_string(&machine).unwrap();
+    assert_eq!(json, r#"{"state":"Review"}"#);
+
+    let mut machine: workflow::StateMachine = serde_json::from_str(&json).unwrap();
+    assert_eq!(machine.state(), &workflow::State::Review);
+    machine.consume(&workflow::Input::Approve(7)).unwrap();
+    assert_eq!(machine.state(), &workflow::State::Approved(7));
+}
+
+#[test]
+fn recorder_is_not_persisted() {
+    let mut machine = workflow::StateMachine::new().with_recorder(VecRecorder::new());
+    machine.consume(&workflow::Input::Submit).unwrap();
+    machine.consume(&workflow::Input::Reject).unwrap();
+    assert_eq!(machine.recorder().len(), 2);
+
+    let json = serde_json::to_string(&machine).unwrap();
+    let machine: StateMachine<workflow::Impl, VecRecorder<workflow::Impl>> =
+        serde_json::from_str(&json).unwrap();
+    assert_eq!(machine.state(), &workflow::State::Draft);
+    assert!(machine.recorder().is_empty());
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(serde(state))]
+    player(Stopped)
+
+    Stopped(Play) => Active,
+    Active(Playing) {
+        Playing(Pause) => Paused,
+        Paused(Play) => Playing,
+    },
+    Active => {
+        Stop => Stopped,
+        Interrupt => Interrupted,
+    },
+    Interrupted(Resume) => Active(H),
+}
+
+#[test]
+fn history_is_persisted() {
+    let mut machine = player::StateMachine::new();
+    machine.consume(&player::Input::Play).unwrap();
+    machine.consume(&player::Input::Pause).unwrap();
+    machine.consume(&player::Input::Interrupt).unwrap();
+
+    let json = serde_json::to_string(&machine).unwrap();
+    assert_eq!(
+        json,
+        r#"{"state":"Interrupted","history":{"Active":"Paused"}}"#
+    );
+
+    let mut machine: player::StateMachine = serde_json::from_str(&json).unwrap();
+    machine.consume(&player::Input::Resume).unwrap();
+    assert_eq!(
+        machine.state(),
+        &player::State::Active(player::ActiveState::Paused)
+    );
+
+    // The state machines persisted without the history start with an empty one
+    let mut machine: player::StateMachine =
+        serde_json::from_str(r#"{"state":"Interrupted"}"#).unwrap();
+    machine.consume(&player::Input::Resume).unwrap();
+    assert_eq!(
+        machine.state(),
+        &player::State::Active(player::ActiveState::Playing)
+    );
+}
@@ -0,0 +1,85 @@
+/// Test for the transitions allowed to share their state and input
+use rust_fsm::*;
+
+// The denied shadowed transitions are checked by the `compile_fail` examples
+// of the crate documentation
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(final(Abandoned))]
+    job(Running)
+
+    Running(Fail(u32)) => Failed,
+    Failed => {
+        // The transition without a guard comes last
+        Retry(u32) if |attempts: &u32| *attempts < 3 => Running,
+        Retry(u32) => Abandoned [GaveUp],
+    },
+    // The same input from another state
+    Abandoned => {
+        Retry(u32) if |_: &u32| false => Running,
+    },
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    player(Stopped)
+
+    Stopped(Play) => Active,
+    Active(Playing) {
+        Playing(Pause) => Paused,
+        // Overrides the transition of the composite state
+        Paused(Stop) => Playing,
+    },
+    Active(Stop) => Stopped,
+}
+
+// The definitions written before the lint keep compiling
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(final(Running, Abandoned))]
+    #[state_machine(allow(shadowed_transitions))]
+    legacy(Failed)
+
+    Failed => {
+        Retry => Running,
+        Retry if true => Abandoned,
+        Retry => Abandoned,
+    },
+}
+
+#[test]
+fn guarded_transitions_before_fallback() {
+    let mut machine = job::StateMachine::new();
+    machine.consume(&job::Input::Fail(1)).unwrap();
+    assert_eq!(machine.consume(&job::Input::Retry(1)), Ok(None));
+    assert_eq!(machine.state(), &job::State::Running);
+
+    machine.consume(&job::Input::Fail(1)).unwrap();
+    assert_eq!(
+        machine.consume(&job::Input::Retry(3)),
+        Ok(Some(job::Output::GaveUp))
+    );
+    assert!(machine.consume(&job::Input::Retry(0)).is_err());
+}
+
+#[test]
+fn substate_overrides_composite_state() {
+    let mut machine = player::StateMachine::new();
+    machine.consume(&player::Input::Play).unwrap();
+    machine.consume(&player::Input::Pause).unwrap();
+    machine.consume(&player::Input::Stop).unwrap();
+    assert_eq!(
+        machine.state(),
+        &player::State::Active(player::ActiveState::Playing)
+    );
+    machine.consume(&player::Input::Stop).unwrap();
+    assert_eq!(machine.state(), &player::State::Stopped);
+}
+
+#[test]
+fn first_transition_taken() {
+    let mut machine = legacy::StateMachine::new();
+    machine.consume(&legacy::Input::Retry).unwrap();
+    assert_eq!(machine.state(), &legacy::State::Running);
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "std")]
+/// Test for the thread-safe shared state machine handle
+use rust_fsm::*;
+use std::time::Duration;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    circuit_breaker(Closed)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open(TimerTriggered) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [SetupTimer]
+    }
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    player(Stopped)
+
+    Stopped(Play) => Active,
+    Active(Playing) {
+        Playing(Pause) => Paused,
+        Paused(Play) => Playing,
+    },
+    Active(Interrupt) => Interrupted,
+    Interrupted(Resume) => Active(H),
+}
+
+type SharedCircuitBreaker = SharedStateMachine<circuit_breaker::Impl>;
+
+// The handle can be shared between threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedCircuitBreaker>();
+    assert_send_sync::<SharedStateMachine<player::Impl, SubstateHistory<player::Impl>>>();
+};
+
+#[test]
+fn shared_circuit_breaker() {
+    let machine = SharedCircuitBreaker::new();
+
+    let res = machine.consume(&circuit_breaker::Input::Unsuccessful);
+    assert_eq!(res, Ok(Some(circuit_breaker::Output::SetupTimer)));
+    assert_eq!(machine.state(), circuit_breaker::State::Open);
+
+    // Set up a timer
+    let machine_wait = machine.clone();
+    let timer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        machine_wait
+            .consume(&circuit_breaker::Input::TimerTriggered)
+            .unwrap();
+    });
+
+    // Try to pass a request when the circuit breaker is still open
+    let res = machine.consume(&circuit_breaker::Input::Successful);
+    assert_eq!(res, Err(TransitionImpossibleError));
+
+    timer.join().unwrap();
+    assert_eq!(machine.state(), circuit_breaker::State::HalfOpen);
+    machine
+        .consume(&circuit_breaker::Input::Successful)
+        .unwrap();
+    assert_eq!(machine.state(), circuit_breaker::State::Closed);
+}
+
+#[test]
+fn consume_if_in_state() {
+    let machine = SharedCircuitBreaker::from_state(circuit_breaker::State::HalfOpen);
+
+    // Many threads race to report a failure of the probe request, only one of
+    // them reopens the circuit breaker
+    let threads = (0..8)
+        .map(|_| {
+            let machine = machine.clone();
+            std::thread::spawn(move || {
+                machine.consume_if(
+                    |state| *state == circuit_breaker::State::HalfOpen,
+                    &circuit_breaker::Input::Unsuccessful,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect::<Vec<_>>();
+
+    let reopened = results.iter().filter(|res| res.is_ok()).count();
+    assert_eq!(reopened, 1);
+    assert!(results
+        .iter()
+        .all(|res| matches!(res, Ok(Some(_)) | Err(ConsumeIfError::ConditionNotMet))));
+    assert_eq!(machine.state(), circuit_breaker::State::Open);
+
+    let res = machine.consume_if(
+        |state| *state == circuit_breaker::State::Open,
+        &circuit_breaker::Input::Successful,
+    );
+    assert_eq!(res, Err(ConsumeIfError::TransitionImpossible));
+}
+
+#[test]
+fn shared_from_state_machine() {
+    let mut machine = circuit_breaker::StateMachine::new();
+    machine
+        .consume(&circuit_breaker::Input::Unsuccessful)
+        .unwrap();
+
+    let shared = SharedStateMachine::from(machine);
+    {
+        let mut machine = shared.lock();
+        machine
+            .consume(&circuit_breaker::Input::TimerTriggered)
+            .unwrap();
+        machine
+            .consume(&circuit_breaker::Input::Successful)
+            .unwrap();
+    }
+    assert_eq!(shared.state(), circuit_breaker::State::Closed);
+    assert_eq!(
+        format!("{shared:?}"),
+        "SharedStateMachine { state: Closed }"
+    );
+}
+
+#[test]
+fn shared_history() {
+    let shared = SharedStateMachine::from(player::StateMachine::new());
+    shared.consume(&player::Input::Play).unwrap();
+    shared.consume(&player::Input::Pause).unwrap();
+    shared.consume(&player::Input::Interrupt).unwrap();
+
+    // The substate is remembered by the shared state machine
+    shared.consume(&player::Input::Resume).unwrap();
+    assert_eq!(
+        shared.state(),
+        player::State::Active(player::ActiveState::Paused)
+    );
+}
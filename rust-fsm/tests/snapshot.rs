@@ -0,0 +1,127 @@
+#![cfg(feature = "std")]
+/// Test for versioned snapshots and migrations between definitions
+use rust_fsm::*;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    order_v1(Pending)
+
+    Pending(Pay) => Done,
+    Done(Cancel) => Pending
+}
+
+// `Pending` is renamed and `Done` is split in two
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    #[state_machine(version(2))]
+    order(AwaitingPayment)
+
+    AwaitingPayment(Pay) => Paid,
+    Paid(Ship) => Shipped(|| 1),
+    Shipped(u32) => {}
+}
+
+#[test]
+fn snapshot_names_states() {
+    let mut machine = order_v1::StateMachine::new();
+    machine.consume(&order_v1::Input::Pay).unwrap();
+    assert_eq!(
+        machine.snapshot(),
+        Ok(Snapshot {
+            version: 0,
+            state: "Done".to_string(),
+        })
+    );
+
+    let machine = order::StateMachine::from_state(order::State::Paid);
+    assert_eq!(
+        machine.snapshot(),
+        Ok(Snapshot {
+            version: 2,
+            state: "Paid".to_string(),
+        })
+    );
+}
+
+#[test]
+fn states_carrying_data_are_not_recorded() {
+    // The data would be lost, so the snapshot could not be restored
+    let machine = order::StateMachine::from_state(order::State::Shipped(3));
+    let err = machine.snapshot().unwrap_err();
+    assert_eq!(err, SnapshotError { state: "Shipped" });
+    assert_eq!(
+        err.to_string(),
+        "the state `Shipped` carries data, which cannot be recorded in a snapshot"
+    );
+}
+
+#[test]
+fn restore_current_version() {
+    let mut machine = order::StateMachine::new();
+    machine.consume(&order::Input::Pay).unwrap();
+    let snapshot = machine.snapshot().unwrap();
+
+    let machine = order::StateMachine::from_snapshot(&snapshot, &Migrations::new()).unwrap();
+    assert_eq!(machine.state(), &order::State::Paid);
+}
+
+#[test]
+fn migrate_old_states() {
+    let migrations = Migrations::<order::Impl>::new()
+        .rename("Pending", order::State::AwaitingPayment)
+        .rename_in(0, "Done", order::State::Paid)
+        .rename_in(1, "Done", order::State::Shipped(0));
+
+    let restore = |version, state: &str| {
+        migrations.restore(&Snapshot {
+            version,
+            state: state.to_string(),
+        })
+    };
+    assert_eq!(restore(0, "Pending"), Ok(order::State::AwaitingPayment));
+    assert_eq!(restore(1, "Pending"), Ok(order::State::AwaitingPayment));
+    assert_eq!(restore(0, "Done"), Ok(order::State::Paid));
+    assert_eq!(restore(1, "Done"), Ok(order::State::Shipped(0)));
+    // Unchanged states are restored by name
+    assert_eq!(restore(1, "Paid"), Ok(order::State::Paid));
+}
+
+#[test]
+fn unmapped_states_fail() {
+    let migrations = Migrations::<order::Impl>::new();
+    let snapshot = Snapshot {
+        version: 0,
+        state: "Done".to_string(),
+    };
+    let err = order::StateMachine::from_snapshot(&snapshot, &migrations).unwrap_err();
+    assert_eq!(
+        err,
+        MigrationError::UnmappedState {
+            version: 0,
+            state: "Done".to_string(),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "the state `Done` of the version 0 is not mapped to any state"
+    );
+
+    // States carrying data cannot be restored by name
+    let snapshot = Snapshot {
+        version: 2,
+        state: "Shipped".to_string(),
+    };
+    assert!(migrations.restore(&snapshot).is_err());
+
+    let snapshot = Snapshot {
+        version: 3,
+        state: "Paid".to_string(),
+    };
+    assert_eq!(
+        migrations.restore(&snapshot),
+        Err(MigrationError::UnsupportedVersion {
+            version: 3,
+            current: 2,
+        })
+    );
+}
@@ -0,0 +1,106 @@
+/// Test for the single-step transition function evaluating guards only once
+use rust_fsm::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static GUARD_CALLS: AtomicU32 = AtomicU32::new(0);
+
+fn checked(amount: &u32) -> bool {
+    GUARD_CALLS.fetch_add(1, Ordering::SeqCst);
+    *amount >= 100
+}
+
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    vending(Idle)
+
+    Idle => {
+        Coin(u32) if |amount: &u32| *amount >= 100 => Ready [Accepted],
+        Coin(u32) => Idle [Refund]
+    },
+    Ready(Push) => Idle [Dispensed]
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    Accepted,
+    Dispensed,
+    Refund(u32),
+}
+
+state_machine! {
+    #[derive(Debug, PartialEq)]
+    #[state_machine(output(crate::Output))]
+    vending_custom(Idle)
+
+    use super::{checked, Output};
+
+    Idle => {
+        Coin(u32) if |amount: &u32| checked(amount) => Ready [Accepted],
+        Coin(u32) => Idle [|amount: &u32| Output::Refund(*amount)]
+    },
+    Ready(Push) => Idle [Dispensed]
+}
+
+#[test]
+fn guards_run_once_per_input() {
+    let mut machine = vending_custom::StateMachine::new();
+
+    let before = GUARD_CALLS.load(Ordering::SeqCst);
+    let res = machine.consume(&vending_custom::Input::Coin(50));
+    assert_eq!(res, Ok(Some(Output::Refund(50))));
+    assert_eq!(GUARD_CALLS.load(Ordering::SeqCst) - before, 1);
+
+    let res = machine.consume(&vending_custom::Input::Coin(100));
+    assert_eq!(res, Ok(Some(Output::Accepted)));
+    assert_eq!(GUARD_CALLS.load(Ordering::SeqCst) - before, 2);
+    assert_eq!(machine.state(), &vending_custom::State::Ready);
+
+    let res = machine.consume(&vending_custom::Input::Push);
+    assert_eq!(res, Ok(Some(Output::Dispensed)));
+    assert_eq!(GUARD_CALLS.load(Ordering::SeqCst) - before, 2);
+}
+
+#[test]
+fn step_matches_transition_and_output() {
+    use vending::{Impl, Input, State};
+
+    for (state, input) in [
+        (State::Idle, Input::Coin(10)),
+        (State::Idle, Input::Coin(200)),
+        (State::Idle, Input::Push),
+        (State::Ready, Input::Push),
+        (State::Ready, Input::Coin(10)),
+    ] {
+        let expected = Impl::transition(&state, &input).map(|new_state| {
+            let output = Impl::output(&state, &input);
+            (new_state, output)
+        });
+        assert_eq!(Impl::step(&state, &input), expected);
+    }
+}
+
+struct Parity;
+
+impl StateMachineImpl for Parity {
+    type Input = u32;
+    type State = bool;
+    type Output = u32;
+    const INITIAL_STATE: Self::State = false;
+
+    fn transition(state: &bool, input: &u32) -> Option<bool> {
+        Some(*state ^ (input % 2 == 1))
+    }
+
+    fn output(_: &bool, input: &u32) -> Option<u32> {
+        Some(input / 2)
+    }
+}
+
+#[test]
+fn default_step_without_dsl() {
+    assert_eq!(Parity::step(&false, &3), Some((true, Some(1))));
+
+    let mut machine: StateMachine<Parity> = StateMachine::new();
+    assert_eq!(machine.consume(&5), Ok(Some(2)));
+    assert!(*machine.state());
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "std")]
+/// Test for timeout transitions driven by a manually advanced clock
+use rust_fsm::*;
+use std::time::Duration;
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    circuit_breaker(Closed)
+
+    Closed(Unsuccessful) => Open [SetupTimer],
+    Open => after(5s) => HalfOpen,
+    HalfOpen => {
+        Successful => Closed,
+        Unsuccessful => Open [SetupTimer],
+        after(1min) => Closed
+    }
+}
+
+#[test]
+fn timeouts_with_mock_clock() {
+    let clock = MockClock::new();
+    let mut machine = TimedStateMachine::<circuit_breaker::Impl, _>::new(&clock);
+    assert_eq!(machine.time_left(), None);
+    assert_eq!(machine.poll_timeout(), None);
+
+    machine
+        .consume(&circuit_breaker::Input::Unsuccessful)
+        .unwrap();
+    assert_eq!(machine.time_left(), Some(Duration::from_secs(5)));
+
+    clock.advance(Duration::from_secs(4));
+    assert_eq!(machine.poll_timeout(), None);
+    assert_eq!(machine.time_left(), Some(Duration::from_secs(1)));
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(machine.poll_timeout(), Some(Ok(None)));
+    assert_eq!(machine.state(), &circuit_breaker::State::HalfOpen);
+    assert_eq!(machine.time_left(), Some(Duration::from_secs(60)));
+
+    // Entering the state again restarts the timer
+    let res = machine.consume(&circuit_breaker::Input::Unsuccessful);
+    assert_eq!(res, Ok(Some(circuit_breaker::Output::SetupTimer)));
+    assert_eq!(machine.time_left(), Some(Duration::from_secs(5)));
+
+    // Timeouts expired in the meantime are fired one by one
+    clock.advance(Duration::from_secs(100));
+    assert_eq!(machine.poll_timeout(), Some(Ok(None)));
+    assert_eq!(machine.state(), &circuit_breaker::State::HalfOpen);
+    assert_eq!(machine.poll_timeout(), Some(Ok(None)));
+    assert_eq!(machine.state(), &circuit_breaker::State::Closed);
+    assert_eq!(machine.poll_timeout(), None);
+    assert_eq!(machine.time_left(), None);
+}
+
+state_machine! {
+    #[derive(Clone, Debug, PartialEq)]
+    retry(Idle)
+
+    Idle(Start) => Waiting(1),
+    Waiting(u32) => after(500ms, Retry) => Running,
+    Running => {
+        Fail => Waiting(2) [Failed],
+        Done => Idle
+    }
+}
+
+#[test]
+fn timeout_with_custom_input() {
+    assert_eq!(
+        <retry::Impl as TimeoutImpl>::timeout(&retry::State::Waiting(1)),
+        Some((Duration::from_millis(500), retry::Input::Retry))
+    );
+    assert_eq!(
+        <retry::Impl as TimeoutImpl>::timeout(&retry::State::Running),
+        None
+    );
+
+    let clock = MockClock::new();
+    let mut machine = TimedStateMachine::<retry::Impl, _>::new(&clock);
+    machine.consume(&retry::Input::Start).unwrap();
+
+    // The timeout input can be consumed before the timeout expires
+    machine.consume(&retry::Input::Retry).unwrap();
+    assert_eq!(machine.state(), &retry::State::Running);
+    assert_eq!(machine.time_left(), None);
+
+    machine.consume(&retry::Input::Fail).unwrap();
+    clock.advance(Duration::from_millis(500));
+    assert_eq!(machine.poll_timeout(), Some(Ok(None)));
+    assert_eq!(machine.state(), &retry::State::Running);
+}
+
+#[test]
+fn timeout_with_system_clock() {
+    let mut machine = TimedStateMachine::<circuit_breaker::Impl, _>::new(SystemClock::new());
+    machine
+        .consume(&circuit_breaker::Input::Unsuccessful)
+        .unwrap();
+    assert!(machine.time_left().unwrap() <= Duration::from_secs(5));
+    assert_eq!(machine.poll_timeout(), None);
+}